@@ -0,0 +1,3 @@
+DROP TABLE tags;
+DROP TABLE tags_meta;
+DROP TABLE posts;
@@ -0,0 +1,25 @@
+-- SQLite can't replay the Postgres history (column renames, `ALTER COLUMN`),
+-- so this creates the schema as of `2021-06-05-092814_add_published_date`.
+
+CREATE TABLE posts (
+    url VARCHAR PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    version VARCHAR NOT NULL,
+    created TIMESTAMP NOT NULL,
+    updated TIMESTAMP NOT NULL,
+    content TEXT NOT NULL DEFAULT '',
+    published TIMESTAMP
+);
+
+CREATE TABLE tags_meta (
+    tag VARCHAR PRIMARY KEY NOT NULL,
+    display BOOLEAN NOT NULL,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE tags (
+    tag VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    PRIMARY KEY (tag, url),
+    CONSTRAINT fk_tag FOREIGN KEY (tag) REFERENCES tags_meta (tag)
+);
@@ -0,0 +1,6 @@
+CREATE TABLE post_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    url VARCHAR NOT NULL,
+    saved TIMESTAMP NOT NULL,
+    snapshot TEXT NOT NULL
+);
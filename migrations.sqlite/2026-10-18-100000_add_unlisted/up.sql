@@ -0,0 +1,2 @@
+ALTER TABLE posts
+ADD COLUMN unlisted BOOLEAN NOT NULL DEFAULT 0;
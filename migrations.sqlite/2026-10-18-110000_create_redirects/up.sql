@@ -0,0 +1,4 @@
+CREATE TABLE redirects (
+    url VARCHAR PRIMARY KEY NOT NULL,
+    target VARCHAR NOT NULL
+);
@@ -0,0 +1,7 @@
+ALTER TABLE posts
+DROP COLUMN series_part;
+
+ALTER TABLE posts
+DROP COLUMN series;
+
+DROP TABLE series;
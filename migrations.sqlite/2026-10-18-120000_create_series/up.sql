@@ -0,0 +1,11 @@
+CREATE TABLE series (
+    name VARCHAR PRIMARY KEY NOT NULL,
+    title VARCHAR NOT NULL,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+ALTER TABLE posts
+ADD COLUMN series VARCHAR REFERENCES series (name);
+
+ALTER TABLE posts
+ADD COLUMN series_part INTEGER;
@@ -0,0 +1,6 @@
+CREATE TABLE post_revisions (
+    id SERIAL PRIMARY KEY,
+    url VARCHAR NOT NULL,
+    saved TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    snapshot TEXT NOT NULL
+);
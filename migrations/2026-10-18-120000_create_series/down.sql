@@ -0,0 +1,5 @@
+ALTER TABLE posts
+DROP COLUMN series,
+DROP COLUMN series_part;
+
+DROP TABLE series;
@@ -0,0 +1,9 @@
+CREATE TABLE series (
+    name VARCHAR PRIMARY KEY,
+    title VARCHAR NOT NULL,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+ALTER TABLE posts
+ADD COLUMN series VARCHAR REFERENCES series (name),
+ADD COLUMN series_part INTEGER;
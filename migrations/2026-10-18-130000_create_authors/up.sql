@@ -0,0 +1,13 @@
+CREATE TABLE authors (
+    name VARCHAR PRIMARY KEY,
+    display_name VARCHAR NOT NULL,
+    email VARCHAR,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE post_authors (
+    url VARCHAR NOT NULL,
+    author VARCHAR NOT NULL REFERENCES authors (name),
+    position INTEGER NOT NULL,
+    PRIMARY KEY (url, author)
+);
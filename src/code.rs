@@ -1,27 +1,261 @@
+use crate::config::CONFIG;
 use lazy_static::lazy_static;
-use std::error::Error;
+use pulldown_cmark::escape::escape_html;
+use std::{error::Error, ops::RangeInclusive};
 use syntect::{
-    easy::HighlightLines, highlighting::ThemeSet, html::*, parsing::SyntaxSet,
+    highlighting::ThemeSet,
+    html::*,
+    parsing::{ParseState, SyntaxReference, SyntaxSet},
     util::LinesWithEndings,
 };
 
+struct Highlighting {
+    syntaxes: SyntaxSet,
+    themes: ThemeSet,
+}
+
 lazy_static! {
-    static ref SYNTAX: SyntaxSet = SyntaxSet::load_defaults_newlines();
-    static ref THEME: ThemeSet = ThemeSet::load_defaults();
+    /// A broken file in `CONFIG.syntax_dir` is kept as its message, as `Box<dyn Error>` can't
+    /// be stored in a static, and reported by the first page that highlights code.
+    static ref HIGHLIGHTING: Result<Highlighting, String> = load().map_err(|e| e.to_string());
+}
+
+/// The default syntaxes and themes, with the ones from `CONFIG.syntax_dir` merged in.
+fn load() -> Result<Highlighting, Box<dyn Error>> {
+    let mut syntaxes = SyntaxSet::load_defaults_newlines();
+    let mut themes = ThemeSet::load_defaults();
+
+    if let Some(dir) = &CONFIG.syntax_dir {
+        let mut builder = syntaxes.into_builder();
+        builder
+            .add_from_folder(dir, true)
+            .map_err(|e| format!("could not load syntaxes from {}: {}", dir, e))?;
+        syntaxes = builder.build();
+
+        let paths = ThemeSet::discover_theme_paths(dir)
+            .map_err(|e| format!("could not load themes from {}: {}", dir, e))?;
+        for path in paths {
+            let name = path
+                .file_stem()
+                .and_then(|name| name.to_str())
+                .ok_or(format!("invalid theme file name {}", path.display()))?;
+            let theme = ThemeSet::get_theme(&path)
+                .map_err(|e| format!("could not load theme {}: {}", path.display(), e))?;
+            themes.themes.insert(name.to_string(), theme);
+        }
+    }
+
+    Ok(Highlighting { syntaxes, themes })
+}
+
+fn highlighting() -> Result<&'static Highlighting, Box<dyn Error>> {
+    HIGHLIGHTING.as_ref().map_err(|e| e.clone().into())
 }
 
-pub fn highlight(code: &str, lang: &str) -> Result<String, Box<dyn Error>> {
-    let syntax = SYNTAX
+/// Prefixed so the highlighting classes can't clash with the ones in `style.css`.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// The info string of a code block, e.g. `rust,linenos,hl=3-5`.
+pub struct CodeInfo {
+    pub lang: String,
+    /// `None` if the language is not known.
+    pub syntax: Option<&'static SyntaxReference>,
+    linenos: bool,
+    highlighted: Vec<RangeInclusive<usize>>,
+}
+
+impl CodeInfo {
+    /// Attributes are separated by commas or spaces, unknown ones (e.g. rustdoc's `ignore`) are skipped.
+    pub fn parse(info: &str) -> Result<Self, Box<dyn Error>> {
+        let mut attributes = info
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|a| !a.is_empty());
+        let lang = attributes.next().unwrap_or_default().to_string();
+
+        let mut linenos = false;
+        let mut highlighted = Vec::new();
+        for attribute in attributes {
+            match attribute.split_once('=') {
+                None if attribute == "linenos" => linenos = true,
+                Some(("hl", lines)) => highlighted.push(
+                    line_range(lines)
+                        .ok_or(format!("invalid line range \"{}\" in \"{}\"", lines, info))?,
+                ),
+                _ => {}
+            }
+        }
+
+        Ok(CodeInfo {
+            syntax: find_syntax(&lang)?,
+            lang,
+            linenos,
+            highlighted,
+        })
+    }
+
+    /// Lines only need their own markup for line numbers or highlighting.
+    fn wrap_lines(&self) -> bool {
+        self.linenos || !self.highlighted.is_empty()
+    }
+}
+
+/// Parses `3` or `3-5`, line numbers start at 1.
+fn line_range(lines: &str) -> Option<RangeInclusive<usize>> {
+    let (from, to) = lines.split_once('-').unwrap_or((lines, lines));
+    let (from, to) = (from.parse().ok()?, to.parse().ok()?);
+    (1 <= from && from <= to).then_some(from..=to)
+}
+
+/// Looks up a language by the aliases in the config, then by name, then by token or extension.
+fn find_syntax(lang: &str) -> Result<Option<&'static SyntaxReference>, Box<dyn Error>> {
+    if lang.is_empty() {
+        return Ok(None);
+    }
+    let syntaxes = &highlighting()?.syntaxes;
+    let lang = CONFIG.code_aliases.get(lang).map_or(lang, String::as_str);
+    Ok(syntaxes
         .find_syntax_by_name(lang)
-        .ok_or(format!("syntax \"{}\" not known", lang))?;
+        .or_else(|| syntaxes.find_syntax_by_token(lang)))
+}
 
-    let mut h = HighlightLines::new(syntax, &THEME.themes["base16-ocean.dark"]);
+/// Renders a whole code block, code in unknown languages is left as plain text.
+pub fn highlight(code: &str, info: &CodeInfo) -> Result<String, Box<dyn Error>> {
+    let lines = match info.syntax {
+        Some(syntax) => highlight_lines(code, syntax, &highlighting()?.syntaxes),
+        None => LinesWithEndings::from(code)
+            .map(|line| {
+                let mut html = String::new();
+                escape_html(&mut html, line).unwrap();
+                html
+            })
+            .collect(),
+    };
 
-    let mut buf = String::new();
-    for line in LinesWithEndings::from(code) {
-        let ranges = h.highlight(line, &SYNTAX);
-        buf += &styled_line_to_highlighted_html(&ranges, IncludeBackground::No);
+    // `hl-code` carries the theme's color for text that no other rule matches
+    let mut classes = Vec::new();
+    if info.syntax.is_some() {
+        classes.push("hl-code".to_string());
+    }
+    if !info.lang.is_empty() {
+        classes.push(format!("language-{}", info.lang));
     }
 
+    let mut buf = String::from(r#"<div class="code"><pre>"#);
+    if classes.is_empty() {
+        buf += "<code>";
+    } else {
+        buf += &format!(r#"<code class="{}">"#, classes.join(" "));
+    }
+    for (i, line) in lines.into_iter().enumerate() {
+        if !info.wrap_lines() {
+            buf += &line;
+            continue;
+        }
+
+        let number = i + 1;
+        let class = if info.highlighted.iter().any(|r| r.contains(&number)) {
+            "line hl"
+        } else {
+            "line"
+        };
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(line) => (line, "\n"),
+            None => (line.as_str(), ""),
+        };
+        buf += &format!(r#"<span class="{}">"#, class);
+        if info.linenos {
+            buf += &format!(r#"<span class="lineno">{}</span>"#, number);
+        }
+        buf += line;
+        buf += "</span>";
+        buf += newline;
+    }
+    buf += "</code></pre></div>\n";
+
     Ok(buf)
 }
+
+/// Highlights every line on its own, i.e. spans still open at the end of a line
+/// are closed there and opened again on the next one.
+fn highlight_lines(code: &str, syntax: &SyntaxReference, syntaxes: &SyntaxSet) -> Vec<String> {
+    let mut state = ParseState::new(syntax);
+    let mut open: Vec<String> = Vec::new();
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(code) {
+        let ops = state.parse_line(line, syntaxes);
+        let (mut spans, _) = tokens_to_classed_spans(line, &ops, CLASS_STYLE);
+        // the newline may be inside of spans, it is put back after all are closed
+        let newline = line.ends_with('\n');
+        if let Some(i) = spans.rfind('\n').filter(|_| newline) {
+            spans.remove(i);
+        }
+
+        let mut html = open.concat();
+        let mut rest = spans.as_str();
+        // text is escaped, so every `<` starts a tag
+        while let Some(start) = rest.find('<') {
+            html += &rest[..start];
+            rest = &rest[start..];
+            let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+            let tag = &rest[..end];
+            if tag == "</span>" {
+                open.pop();
+            } else {
+                open.push(tag.to_string());
+            }
+            html += tag;
+            rest = &rest[end..];
+        }
+        html += rest;
+        html += &"</span>".repeat(open.len());
+        if newline {
+            html += "\n";
+        }
+        lines.push(html);
+    }
+    lines
+}
+
+/// The colors of the given theme for the classes `highlight` emits.
+pub fn stylesheet(theme: &str) -> Result<String, Box<dyn Error>> {
+    let theme = highlighting()?
+        .themes
+        .themes
+        .get(theme)
+        .ok_or(format!("theme \"{}\" not known", theme))?;
+
+    Ok(css_for_theme_with_class_style(theme, CLASS_STYLE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_line_ranges() {
+        assert_eq!(line_range("3"), Some(3..=3));
+        assert_eq!(line_range("3-5"), Some(3..=5));
+        assert_eq!(line_range("0"), None);
+        assert_eq!(line_range("5-3"), None);
+        assert_eq!(line_range("a-b"), None);
+        assert_eq!(line_range(""), None);
+    }
+
+    #[test]
+    fn reopens_spans_on_every_line() {
+        let syntaxes = SyntaxSet::load_defaults_newlines();
+        let syntax = syntaxes.find_syntax_by_token("rust").unwrap();
+        let lines = highlight_lines("/* a\nb */\nc\n", syntax, &syntaxes);
+        assert_eq!(lines.len(), 3);
+
+        for line in lines.iter() {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count()
+            );
+            assert_eq!(line.find('\n'), Some(line.len() - 1));
+        }
+        // the comment is still open on the second line
+        assert!(lines[1].starts_with("<span") && lines[1].contains("comment"));
+    }
+}
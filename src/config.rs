@@ -1,11 +1,88 @@
 use lazy_static::lazy_static;
 use serde::Deserialize;
-use serde_json;
+use std::collections::HashMap;
+
+/// What the RSS feed carries for each post.
+#[derive(Clone, Copy, Default, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    #[default]
+    Full,
+    /// The excerpt and a link to the post, the full post if it has no excerpt.
+    Excerpt,
+}
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Config {
     pub url: String,
     pub ssh_url: String,
+    /// Owner of the site, credited for posts without authors.
+    #[serde(default = "default_author")]
+    pub author: String,
+    #[serde(default = "default_title")]
+    pub title: String,
+    /// Describes the site on the overview in search results and link previews.
+    pub description: Option<String>,
+    /// Syntect theme used for `syntax.css`.
+    #[serde(default = "default_syntax_theme")]
+    pub syntax_theme: String,
+    /// Extra names for code block languages, e.g. `"jsonc": "JSON"`.
+    #[serde(default)]
+    pub code_aliases: HashMap<String, String>,
+    /// Directory with additional `.sublime-syntax` and `.tmTheme` files.
+    pub syntax_dir: Option<String>,
+    /// Directory with the `<name>.html` templates of shortcodes. These are not Tera templates
+    /// like the ones in `template_dir`: `{{ key }}` is replaced by the html escaped argument,
+    /// `{{ key? }}` may be left out, and there are no filters, loops or conditionals.
+    #[serde(default = "default_shortcode_dir")]
+    pub shortcode_dir: String,
+    /// Widths local images are resized to, in addition to their original size.
+    #[serde(default = "default_image_widths")]
+    pub image_widths: Vec<u32>,
+    /// Where resized images are kept between renders.
+    #[serde(default = "default_image_cache")]
+    pub image_cache: String,
+    #[serde(default)]
+    pub feed_content: FeedContent,
+    /// Posts per page of the overview and the tag pages.
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+    /// Directory with templates replacing the ones of the default theme. Values are html
+    /// escaped, except for the rendered `body`, which has to be passed through `| safe`.
+    #[serde(default = "default_template_dir")]
+    pub template_dir: String,
+}
+
+fn default_author() -> String {
+    "Jonas Maier".into()
+}
+
+fn default_title() -> String {
+    "Jonas' personal website".into()
+}
+
+fn default_syntax_theme() -> String {
+    "base16-ocean.dark".into()
+}
+
+fn default_shortcode_dir() -> String {
+    "shortcodes".into()
+}
+
+fn default_image_widths() -> Vec<u32> {
+    vec![400, 800, 1600]
+}
+
+fn default_image_cache() -> String {
+    ".cache/images".into()
+}
+
+fn default_page_size() -> usize {
+    20
+}
+
+fn default_template_dir() -> String {
+    "templates".into()
 }
 
 lazy_static! {
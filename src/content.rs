@@ -0,0 +1,443 @@
+use super::*;
+use chrono::Datelike;
+use models::{Author, Post, PostAuthor, Redirect, Series, Tag, TagMeta};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+/// Frontmatter of a tag description file, which may also be left out entirely.
+#[derive(Serialize, Deserialize)]
+struct TagFileMeta {
+    display: bool,
+}
+
+/// Everything that is needed to render the site,
+/// loaded either from the database or from a directory of Markdown files.
+pub struct Content {
+    /// sorted by creation date, oldest first
+    pub posts: Vec<Post>,
+    pub tags: Vec<Tag>,
+    /// sorted by tag name
+    pub tags_meta: Vec<TagMeta>,
+    pub redirects: Vec<Redirect>,
+    pub series: Vec<Series>,
+    pub authors: Vec<Author>,
+    /// sorted by position
+    pub post_authors: Vec<PostAuthor>,
+    /// by post url, filled in by `count_words` ahead of rendering
+    pub statistics: HashMap<String, render::Statistics>,
+}
+
+impl Content {
+    pub fn load_from_db(db: &DbConnection) -> AResult<Self> {
+        use crate::schema::*;
+        let posts = posts::table
+            .order_by((posts::created.asc(), posts::url.asc()))
+            .load::<Post>(db)?;
+        let tags = tags::table.load::<Tag>(db)?;
+        let tags_meta = tags_meta::table
+            .order_by(tags_meta::tag.asc())
+            .load::<TagMeta>(db)?;
+        let redirects = redirects::table.load::<Redirect>(db)?;
+        let series = series::table.load::<Series>(db)?;
+        let authors = authors::table.load::<Author>(db)?;
+        let post_authors = post_authors::table
+            .order_by(post_authors::position.asc())
+            .load::<PostAuthor>(db)?;
+        Ok(Self {
+            posts,
+            tags,
+            tags_meta,
+            redirects,
+            series,
+            authors,
+            post_authors,
+            statistics: HashMap::new(),
+        })
+    }
+
+    /// Reads every `<url>.md` file in `dir` as a post in the same format as the editor uses,
+    /// tag descriptions are read from `<dir>/tags/<tag>.md`, series from `<dir>/series/<name>.md`,
+    /// authors from `<dir>/authors/<name>.md` and renamed posts from `<dir>/redirects.yaml`.
+    pub fn load_from_dir(dir: &str) -> AResult<Self> {
+        let mut content = Self::read_dir(dir)?;
+        for tag in content.tags.iter() {
+            if content.tag_meta(&tag.tag).is_none() {
+                content.tags_meta.push(TagMeta {
+                    tag: tag.tag.clone(),
+                    display: true,
+                    description: String::new(),
+                });
+            }
+        }
+        content.tags_meta.sort_by(|a, b| a.tag.cmp(&b.tag));
+        for post in content.posts.iter() {
+            if let Some(name) = &post.series {
+                if content.series(name).is_none() {
+                    content.series.push(Series::new(name));
+                }
+            }
+        }
+        for post_author in content.post_authors.iter() {
+            if content.author(&post_author.author).is_none() {
+                content.authors.push(Author::new(&post_author.author));
+            }
+        }
+        Ok(content)
+    }
+
+    /// Reads a content directory, but unlike `load_from_dir` doesn't make up
+    /// entries for tags, series and authors that have no file of their own.
+    fn read_dir(dir: &str) -> AResult<Self> {
+        let mut posts = Vec::new();
+        let mut tags = Vec::new();
+        let mut post_authors = Vec::new();
+
+        for (url, path) in markdown_files(dir)? {
+            let post =
+                editing::Post::new_from_file(&path).map_err(|err| format!("{}: {}", path, err))?;
+            let meta = post.meta;
+
+            let created = meta
+                .created
+                .ok_or(format!("{}: missing `created` date", path))?;
+            let published = if meta.published {
+                Some(meta.published_at.unwrap_or(created))
+            } else {
+                None
+            };
+
+            tags.extend(meta.tags.into_iter().map(|tag| Tag {
+                tag,
+                url: url.clone(),
+            }));
+            post_authors.extend(meta.authors.into_iter().enumerate().map(|(i, author)| {
+                PostAuthor {
+                    url: url.clone(),
+                    author,
+                    position: i as i32,
+                }
+            }));
+            posts.push(Post {
+                url,
+                title: meta.title,
+                version: meta.version,
+                created,
+                updated: meta.updated.unwrap_or(created),
+                content: post.content,
+                published,
+                unlisted: meta.unlisted,
+                series: meta.series,
+                series_part: meta.series_part,
+                toc: meta.toc,
+                summary: meta.summary,
+            });
+        }
+
+        let tag_dir = format!("{}/tags", dir);
+        let mut tags_meta = Vec::new();
+        if Path::new(&tag_dir).is_dir() {
+            for (tag, path) in markdown_files(&tag_dir)? {
+                let file = std::fs::read_to_string(&path)?;
+                let (meta, description) = if file.starts_with("---") {
+                    editing::split_frontmatter::<TagFileMeta>(&file)
+                        .map_err(|err| format!("{}: {}", path, err))?
+                } else {
+                    (TagFileMeta { display: true }, file)
+                };
+                tags_meta.push(TagMeta {
+                    tag,
+                    display: meta.display,
+                    description,
+                });
+            }
+        }
+
+        let series_dir = format!("{}/series", dir);
+        let mut series = Vec::new();
+        if Path::new(&series_dir).is_dir() {
+            for (name, path) in markdown_files(&series_dir)? {
+                let (meta, description) = editing::split_frontmatter::<editing::SeriesMeta>(
+                    &std::fs::read_to_string(&path)?,
+                )
+                .map_err(|err| format!("{}: {}", path, err))?;
+                series.push(Series {
+                    name,
+                    title: meta.title,
+                    description,
+                });
+            }
+        }
+
+        let author_dir = format!("{}/authors", dir);
+        let mut authors = Vec::new();
+        if Path::new(&author_dir).is_dir() {
+            for (name, path) in markdown_files(&author_dir)? {
+                let (meta, description) = editing::split_frontmatter::<editing::AuthorMeta>(
+                    &std::fs::read_to_string(&path)?,
+                )
+                .map_err(|err| format!("{}: {}", path, err))?;
+                authors.push(Author {
+                    name,
+                    display_name: meta.display_name,
+                    email: meta.email,
+                    description,
+                });
+            }
+        }
+
+        let redirect_file = format!("{}/redirects.yaml", dir);
+        let redirects = if Path::new(&redirect_file).is_file() {
+            serde_yaml::from_str(&std::fs::read_to_string(&redirect_file)?)
+                .map_err(|err| format!("{}: {}", redirect_file, err))?
+        } else {
+            Vec::new()
+        };
+
+        posts.sort_by(|a, b| (a.created, &a.url).cmp(&(b.created, &b.url)));
+
+        Ok(Self {
+            posts,
+            tags,
+            tags_meta,
+            redirects,
+            series,
+            authors,
+            post_authors,
+            statistics: HashMap::new(),
+        })
+    }
+
+    /// The post as the editor would show it, but including all timestamps.
+    fn editable(&self, post: &Post) -> editing::Post {
+        let tags = self.tags_of(&post.url).map(|t| t.tag.clone()).collect();
+        let authors = self
+            .post_authors
+            .iter()
+            .filter(|a| a.url == post.url)
+            .map(|a| a.author.clone())
+            .collect();
+        editing::Post::from_model(post, tags, authors)
+    }
+
+    /// Writes everything to `dir` in the format `load_from_dir` reads.
+    pub fn write_to_dir(&self, dir: &str) -> AResult<()> {
+        std::fs::create_dir_all(format!("{}/tags", dir))?;
+        std::fs::create_dir_all(format!("{}/series", dir))?;
+        std::fs::create_dir_all(format!("{}/authors", dir))?;
+
+        for post in self.posts.iter() {
+            self.editable(post)
+                .write_to_file(&format!("{}/{}.md", dir, post.url))?;
+        }
+
+        for meta in self.tags_meta.iter() {
+            let file = editing::join_frontmatter(
+                &TagFileMeta {
+                    display: meta.display,
+                },
+                &meta.description,
+            )?;
+            std::fs::write(format!("{}/tags/{}.md", dir, meta.tag), file)?;
+        }
+
+        for series in self.series.iter() {
+            let file = editing::join_frontmatter(
+                &editing::SeriesMeta {
+                    title: series.title.clone(),
+                },
+                &series.description,
+            )?;
+            std::fs::write(format!("{}/series/{}.md", dir, series.name), file)?;
+        }
+
+        for author in self.authors.iter() {
+            let file = editing::join_frontmatter(
+                &editing::AuthorMeta {
+                    display_name: author.display_name.clone(),
+                    email: author.email.clone(),
+                },
+                &author.description,
+            )?;
+            std::fs::write(format!("{}/authors/{}.md", dir, author.name), file)?;
+        }
+
+        if !self.redirects.is_empty() {
+            let file = serde_yaml::to_string(&self.redirects)?;
+            std::fs::write(format!("{}/redirects.yaml", dir), file)?;
+        }
+
+        Ok(())
+    }
+
+    /// Imports a content directory, replacing everything with the same name.
+    pub fn import(dir: &str, db: &DbConnection) -> AResult<()> {
+        use crate::schema::*;
+        use diesel::dsl::*;
+
+        let content = Self::read_dir(dir)?;
+
+        db.transaction(|| {
+            for meta in content.tags_meta.iter() {
+                meta.write_to_db(db)?;
+            }
+
+            let known = tags_meta::table.select(tags_meta::tag).load::<String>(db)?;
+            let mut missing = content
+                .tags
+                .iter()
+                .filter(|t| !known.contains(&t.tag))
+                .map(|t| TagMeta {
+                    tag: t.tag.clone(),
+                    display: true,
+                    description: String::new(),
+                })
+                .collect::<Vec<_>>();
+            missing.sort_by(|a, b| a.tag.cmp(&b.tag));
+            missing.dedup_by(|a, b| a.tag == b.tag);
+            insert_into(tags_meta::table).values(&missing).execute(db)?;
+
+            for series in content.series.iter() {
+                series.write_to_db(db)?;
+            }
+            for author in content.authors.iter() {
+                author.write_to_db(db)?;
+            }
+            for post_author in content.post_authors.iter() {
+                Author::create_if_missing(&post_author.author, db)?;
+            }
+
+            for post in content.posts.iter() {
+                if let Some(series) = &post.series {
+                    Series::create_if_missing(series, db)?;
+                }
+                println!("importing post {}.", post.url);
+                revisions::snapshot(&post.url, &content.editable(post), db)?;
+                delete(tags::table.filter(tags::url.eq(&post.url))).execute(db)?;
+                delete(post_authors::table.filter(post_authors::url.eq(&post.url))).execute(db)?;
+                delete(posts::table.filter(posts::url.eq(&post.url))).execute(db)?;
+                insert_into(posts::table).values(post).execute(db)?;
+            }
+            insert_into(tags::table).values(&content.tags).execute(db)?;
+            insert_into(post_authors::table)
+                .values(&content.post_authors)
+                .execute(db)?;
+
+            for redirect in content.redirects.iter() {
+                delete(redirects::table.filter(redirects::url.eq(&redirect.url))).execute(db)?;
+                insert_into(redirects::table).values(redirect).execute(db)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// All posts that are published by now and not unlisted, in order of publication.
+    pub fn listed(&self) -> impl DoubleEndedIterator<Item = &Post> {
+        let mut listed = self
+            .posts
+            .iter()
+            .filter(|p| p.is_published() && !p.unlisted)
+            .collect::<Vec<_>>();
+        listed.sort_by_key(|p| (p.published, p.created));
+        listed.into_iter()
+    }
+
+    pub fn tags_of<'a>(&'a self, url: &'a str) -> impl Iterator<Item = &'a Tag> {
+        self.tags.iter().filter(move |t| t.url == url)
+    }
+
+    pub fn tag_meta(&self, tag: &str) -> Option<&TagMeta> {
+        self.tags_meta.iter().find(|m| m.tag == tag)
+    }
+
+    /// Hidden tags still get a page, but are left out of the tag index and the tags of posts.
+    pub fn is_displayed(&self, tag: &str) -> bool {
+        self.tag_meta(tag).is_none_or(|m| m.display)
+    }
+
+    pub fn series(&self, name: &str) -> Option<&Series> {
+        self.series.iter().find(|s| s.name == name)
+    }
+
+    pub fn author(&self, name: &str) -> Option<&Author> {
+        self.authors.iter().find(|a| a.name == name)
+    }
+
+    /// The credited authors of a post, in order.
+    pub fn authors_of(&self, url: &str) -> Vec<&Author> {
+        self.post_authors
+            .iter()
+            .filter(|a| a.url == url)
+            .filter_map(|a| self.author(&a.author))
+            .collect()
+    }
+
+    /// The listed posts of a series, in order.
+    pub fn series_parts(&self, name: &str) -> Vec<&Post> {
+        let mut parts = self
+            .listed()
+            .filter(|p| p.series.as_deref() == Some(name))
+            .collect::<Vec<_>>();
+        sort_parts(&mut parts);
+        parts
+    }
+
+    /// The listed posts by the year and month they were published in, oldest first.
+    pub fn archive(&self) -> BTreeMap<i32, BTreeMap<u32, Vec<&Post>>> {
+        let mut archive: BTreeMap<_, BTreeMap<_, Vec<_>>> = BTreeMap::new();
+        for post in self.listed() {
+            if let Some(published) = post.published {
+                archive
+                    .entry(published.year())
+                    .or_default()
+                    .entry(published.month())
+                    .or_default()
+                    .push(post);
+            }
+        }
+        for posts in archive.values_mut().flat_map(|months| months.values_mut()) {
+            posts.sort_by_key(|p| p.published);
+        }
+        archive
+    }
+
+    /// Counts the words of every post, so rendering them doesn't have to for every listing.
+    pub fn count_words(&mut self) -> AResult<()> {
+        for post in self.posts.iter() {
+            let statistics = render::statistics(post)?;
+            self.statistics.insert(post.url.clone(), statistics);
+        }
+        Ok(())
+    }
+
+    pub fn statistics_of(&self, url: &str) -> &render::Statistics {
+        &self.statistics[url]
+    }
+}
+
+pub fn sort_parts(parts: &mut [&Post]) {
+    parts.sort_by_key(|p| (p.series_part.unwrap_or(i32::MAX), p.created));
+}
+
+/// Lists the `.md` files of a directory as pairs of file stem and path.
+fn markdown_files(dir: &str) -> AResult<Vec<(String, String)>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension() != Some("md".as_ref()) {
+            continue;
+        }
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or(format!("invalid file name `{}`", path.display()))?;
+        let path_str = path
+            .to_str()
+            .ok_or(format!("invalid file name `{}`", path.display()))?;
+        files.push((stem.to_string(), path_str.to_string()));
+    }
+    Ok(files)
+}
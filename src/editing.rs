@@ -1,5 +1,6 @@
 use super::*;
-use serde::{Deserialize, Serialize};
+use chrono::NaiveDateTime;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PostMeta {
@@ -8,7 +9,31 @@ pub struct PostMeta {
     pub version: String,
     pub published: bool,
     #[serde(default)]
+    pub unlisted: bool,
+    #[serde(default)]
     pub tags: Vec<String>,
+    /// Names of the authors, the site owner is credited if there are none.
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series_part: Option<i32>,
+    /// Shows a table of contents at the `[TOC]` marker or at the top of the post.
+    #[serde(default)]
+    pub toc: bool,
+    /// Excerpt for the overview and feed, instead of everything before `<!-- more -->`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    // `created` and `updated` are only read when rendering from or importing a content directory,
+    // the editor leaves them to the database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<NaiveDateTime>,
+    /// Publishing date in UTC, may be in the future to schedule a post.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<NaiveDateTime>,
 }
 
 pub struct Post {
@@ -22,11 +47,7 @@ impl Post {
         let mut content = String::new();
         file.read_to_string(&mut content)?;
 
-        let mut parts = content.split("---");
-        parts.next().ok_or("missing metadata")?;
-        let meta = parts.next().ok_or("missing metadata")?;
-        let markdown = parts.collect::<Vec<_>>().join("---");
-        let meta = serde_yaml::from_str::<PostMeta>(meta)?;
+        let (meta, markdown) = split_frontmatter(&content)?;
 
         Ok(Self {
             meta,
@@ -35,13 +56,33 @@ impl Post {
     }
 
     pub fn write_to_file(&self, path: &str) -> AResult<()> {
-        let mut buffer = serde_yaml::to_string(&self.meta)?;
-        buffer += &format!("---{}", self.content);
-        std::fs::write(path, buffer)?;
+        std::fs::write(path, join_frontmatter(&self.meta, &self.content)?)?;
         Ok(())
     }
 
-    pub fn new_from_db(name: &str, db: &PgConnection) -> AResult<Self> {
+    /// Like `new_from_db`, but keeps all timestamps of the post.
+    pub fn from_model(post: &models::Post, tags: Vec<String>, authors: Vec<String>) -> Self {
+        Self {
+            meta: PostMeta {
+                title: post.title.clone(),
+                version: post.version.clone(),
+                published: post.published.is_some(),
+                unlisted: post.unlisted,
+                tags,
+                authors,
+                series: post.series.clone(),
+                series_part: post.series_part,
+                toc: post.toc,
+                summary: post.summary.clone(),
+                created: Some(post.created),
+                updated: Some(post.updated),
+                published_at: post.published,
+            },
+            content: post.content.clone(),
+        }
+    }
+
+    pub fn new_from_db(name: &str, db: &DbConnection) -> AResult<Self> {
         let post = models::Post::load_from_db(name, db)?;
 
         let tags = {
@@ -49,26 +90,45 @@ impl Post {
             tags.filter(url.eq(name)).select(tag).load::<String>(db)?
         };
 
+        let authors = {
+            use crate::schema::post_authors::dsl::*;
+            post_authors
+                .filter(url.eq(name))
+                .order_by(position)
+                .select(author)
+                .load::<String>(db)?
+        };
+
         Ok(Self {
             meta: PostMeta {
                 title: post.title,
                 version: post.version,
                 published: post.published.is_some(),
+                unlisted: post.unlisted,
                 tags,
+                authors,
+                series: post.series,
+                series_part: post.series_part,
+                toc: post.toc,
+                summary: post.summary,
+                created: None,
+                updated: None,
+                published_at: post.published,
             },
             content: post.content,
         })
     }
 
-    pub fn write_to_db(self, name: &str, db: &PgConnection) -> AResult<()> {
+    pub fn write_to_db(self, name: &str, db: &DbConnection) -> AResult<()> {
+        revisions::snapshot(name, &self, db)?;
+
         let orig_post = models::Post::load_from_db(name, db);
 
-        let published = orig_post
-            .as_ref()
-            .ok()
-            .map(|p| p.published)
-            .flatten()
-            .unwrap_or(models::now());
+        let published = self
+            .meta
+            .published_at
+            .or_else(|| orig_post.as_ref().ok().and_then(|p| p.published))
+            .unwrap_or_else(models::now);
         let published = if self.meta.published {
             Some(published)
         } else {
@@ -80,14 +140,23 @@ impl Post {
             created: orig_post
                 .as_ref()
                 .map(|p| p.created)
-                .unwrap_or(models::now()),
+                .unwrap_or_else(|_| models::now()),
             updated: models::now(),
             title: self.meta.title,
             version: self.meta.version,
             content: self.content,
             published,
+            unlisted: self.meta.unlisted,
+            series: self.meta.series,
+            series_part: self.meta.series_part,
+            toc: self.meta.toc,
+            summary: self.meta.summary,
         };
 
+        if let Some(series) = &edited.series {
+            models::Series::create_if_missing(series, db)?;
+        }
+
         use diesel::dsl::*;
 
         {
@@ -115,10 +184,50 @@ impl Post {
             insert_into(tags).values(&tag_tuples).execute(db)?;
         }
 
+        {
+            use crate::schema::post_authors::dsl::*;
+
+            let author_tuples = self
+                .meta
+                .authors
+                .into_iter()
+                .enumerate()
+                .map(|(i, a)| models::PostAuthor {
+                    url: name.into(),
+                    author: a,
+                    position: i as i32,
+                })
+                .collect::<Vec<_>>();
+
+            for a in author_tuples.iter() {
+                models::Author::create_if_missing(&a.author, db)?;
+            }
+            delete(post_authors.filter(url.eq(name))).execute(db)?;
+            insert_into(post_authors)
+                .values(&author_tuples)
+                .execute(db)?;
+        }
+
         Ok(())
     }
 }
 
+/// Splits a file into its YAML frontmatter and the Markdown following it.
+pub fn split_frontmatter<T: DeserializeOwned>(content: &str) -> AResult<(T, String)> {
+    let mut parts = content.split("---");
+    parts.next().ok_or("missing metadata")?;
+    let meta = parts.next().ok_or("missing metadata")?;
+    let markdown = parts.collect::<Vec<_>>().join("---");
+    let meta = serde_yaml::from_str::<T>(meta)?;
+    Ok((meta, markdown))
+}
+
+pub fn join_frontmatter<T: Serialize>(meta: &T, content: &str) -> AResult<String> {
+    let mut buffer = serde_yaml::to_string(meta)?;
+    buffer += &format!("---{}", content);
+    Ok(buffer)
+}
+
 const EDIT_PATH: &str = ".edit.md";
 fn open_editor() -> AResult<()> {
     std::process::Command::new("/usr/bin/sh")
@@ -129,8 +238,8 @@ fn open_editor() -> AResult<()> {
     Ok(())
 }
 
-pub fn edit_post(url: &str, db: &PgConnection) -> AResult<()> {
-    db.build_transaction().run(|| {
+pub fn edit_post(url: &str, db: &DbConnection) -> AResult<()> {
+    db.transaction(|| {
         match Post::new_from_db(url, db) {
             Ok(post) => post.write_to_file(EDIT_PATH)?,
             Err(_) => std::fs::write(EDIT_PATH, vec![])?,
@@ -147,7 +256,7 @@ pub fn edit_post(url: &str, db: &PgConnection) -> AResult<()> {
                     let mut input = String::new();
                     std::io::stdin().read_line(&mut input)?;
 
-                    if input.starts_with("q") {
+                    if input.starts_with('q') {
                         return Err(err);
                     }
                 }
@@ -160,12 +269,11 @@ pub fn edit_post(url: &str, db: &PgConnection) -> AResult<()> {
     })
 }
 
-pub fn edit_tag(name: &str, db: &PgConnection) -> AResult<()> {
+pub fn edit_tag(name: &str, db: &DbConnection) -> AResult<()> {
     use crate::models::*;
     use crate::schema::tags_meta::dsl::*;
-    use diesel::dsl::*;
 
-    db.build_transaction().run(|| {
+    db.transaction(|| {
         let meta = tags_meta.filter(tag.eq(name)).load::<TagMeta>(db)?;
         let mut meta = meta.into_iter().next().unwrap_or(TagMeta {
             tag: name.into(),
@@ -177,13 +285,205 @@ pub fn edit_tag(name: &str, db: &PgConnection) -> AResult<()> {
         open_editor()?;
         meta.description = std::fs::read_to_string(EDIT_PATH)?;
 
-        insert_into(tags_meta)
-            .values(&meta)
-            .on_conflict(tag)
-            .do_update()
-            .set(&meta)
+        meta.write_to_db(db)
+    })
+}
+
+/// Shows or hides a tag in the tag index and the tags of posts.
+pub fn toggle_tag(name: &str, db: &DbConnection) -> AResult<()> {
+    use crate::models::*;
+    use crate::schema::tags_meta::dsl::*;
+
+    db.transaction(|| {
+        let mut meta = tags_meta
+            .filter(tag.eq(name))
+            .load::<TagMeta>(db)?
+            .into_iter()
+            .next()
+            .ok_or(format!("no tag with name `{}`.", name))?;
+        meta.display = !meta.display;
+        println!(
+            "tag {} is now {}.",
+            name,
+            if meta.display { "shown" } else { "hidden" }
+        );
+
+        meta.write_to_db(db)
+    })
+}
+
+/// Moves a post and everything referring to it to a new url,
+/// the old url keeps working as a redirect.
+pub fn rename_post(old: &str, new: &str, db: &DbConnection) -> AResult<()> {
+    use crate::schema::*;
+    use diesel::dsl::*;
+
+    db.transaction(|| {
+        let mut post = models::Post::load_from_db(old, db)?;
+        if models::Post::load_from_db(new, db).is_ok() {
+            Err(format!("there already is a post with name `{}`.", new))?;
+        }
+
+        post.url = new.into();
+        insert_into(posts::table).values(&post).execute(db)?;
+        update(tags::table.filter(tags::url.eq(old)))
+            .set(tags::url.eq(new))
+            .execute(db)?;
+        update(post_authors::table.filter(post_authors::url.eq(old)))
+            .set(post_authors::url.eq(new))
+            .execute(db)?;
+        update(post_revisions::table.filter(post_revisions::url.eq(old)))
+            .set(post_revisions::url.eq(new))
+            .execute(db)?;
+        delete(posts::table.filter(posts::url.eq(old))).execute(db)?;
+
+        // older redirects are pointed to the new url directly instead of chaining them
+        delete(redirects::table.filter(redirects::url.eq(new))).execute(db)?;
+        update(redirects::table.filter(redirects::target.eq(old)))
+            .set(redirects::target.eq(new))
+            .execute(db)?;
+        insert_into(redirects::table)
+            .values(models::Redirect {
+                url: old.into(),
+                target: new.into(),
+            })
             .execute(db)?;
 
         Ok(())
     })
 }
+
+/// Deletes a post after asking for confirmation, its revisions are kept.
+pub fn delete_post(name: &str, db: &DbConnection) -> AResult<()> {
+    use crate::schema::*;
+    use diesel::dsl::*;
+
+    let post = models::Post::load_from_db(name, db)?;
+
+    print!("Delete post `{}` ({})? [y/N] ", name, post.title);
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    if !input.trim().eq_ignore_ascii_case("y") {
+        println!("Nothing deleted.");
+        return Ok(());
+    }
+
+    db.transaction::<_, Box<dyn Error>, _>(|| {
+        revisions::save_current(name, db)?;
+        delete(tags::table.filter(tags::url.eq(name))).execute(db)?;
+        delete(post_authors::table.filter(post_authors::url.eq(name))).execute(db)?;
+        delete(redirects::table.filter(redirects::target.eq(name))).execute(db)?;
+        delete(posts::table.filter(posts::url.eq(name))).execute(db)?;
+        Ok(())
+    })?;
+    if revisions::has_revisions(name, db)? {
+        println!(
+            "Deleted post `{}`, it can be brought back with --restore.",
+            name
+        );
+    } else {
+        println!("Deleted post `{}`.", name);
+    }
+
+    for tag in orphaned_tags(db)? {
+        println!(
+            "Tag `{}` isn't used by any post anymore, remove it with --prune-tags.",
+            tag
+        );
+    }
+
+    Ok(())
+}
+
+/// Tags that have a description, but no posts.
+fn orphaned_tags(db: &DbConnection) -> AResult<Vec<String>> {
+    use crate::schema::*;
+
+    let used = tags::table.select(tags::tag).load::<String>(db)?;
+    let orphans = tags_meta::table
+        .select(tags_meta::tag)
+        .order_by(tags_meta::tag)
+        .load::<String>(db)?
+        .into_iter()
+        .filter(|t| !used.contains(t))
+        .collect();
+    Ok(orphans)
+}
+
+pub fn prune_tags(db: &DbConnection) -> AResult<()> {
+    use crate::schema::tags_meta::dsl::*;
+    use diesel::dsl::*;
+
+    db.transaction(|| {
+        for orphan in orphaned_tags(db)? {
+            println!("removing tag {}.", orphan);
+            delete(tags_meta.filter(tag.eq(orphan))).execute(db)?;
+        }
+        Ok(())
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SeriesMeta {
+    pub title: String,
+}
+
+pub fn edit_series(name: &str, db: &DbConnection) -> AResult<()> {
+    use crate::models::Series;
+    use crate::schema::series::dsl as s;
+
+    db.transaction(|| {
+        let meta = s::series.filter(s::name.eq(name)).load::<Series>(db)?;
+        let mut meta = meta.into_iter().next().unwrap_or_else(|| Series::new(name));
+
+        let file = join_frontmatter(
+            &SeriesMeta {
+                title: meta.title.clone(),
+            },
+            &meta.description,
+        )?;
+        std::fs::write(EDIT_PATH, file)?;
+        open_editor()?;
+        let file = std::fs::read_to_string(EDIT_PATH)?;
+        let (edited, description) = split_frontmatter::<SeriesMeta>(&file)?;
+        meta.title = edited.title;
+        meta.description = description;
+
+        meta.write_to_db(db)
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuthorMeta {
+    pub display_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+pub fn edit_author(name: &str, db: &DbConnection) -> AResult<()> {
+    use crate::models::Author;
+    use crate::schema::authors::dsl as a;
+
+    db.transaction(|| {
+        let meta = a::authors.filter(a::name.eq(name)).load::<Author>(db)?;
+        let mut meta = meta.into_iter().next().unwrap_or_else(|| Author::new(name));
+
+        let file = join_frontmatter(
+            &AuthorMeta {
+                display_name: meta.display_name.clone(),
+                email: meta.email.clone(),
+            },
+            &meta.description,
+        )?;
+        std::fs::write(EDIT_PATH, file)?;
+        open_editor()?;
+        let file = std::fs::read_to_string(EDIT_PATH)?;
+        let (edited, description) = split_frontmatter::<AuthorMeta>(&file)?;
+        meta.display_name = edited.display_name;
+        meta.email = edited.email;
+        meta.description = description;
+
+        meta.write_to_db(db)
+    })
+}
@@ -0,0 +1,102 @@
+use crate::config::CONFIG;
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use std::{error::Error, fs, path::Path};
+
+/// A local image with its resized variants, as urls with their widths.
+pub struct Image {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<(String, u32)>,
+}
+
+const FORMATS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+/// Resizes a local image like `/img/a.png` from `static_html` to all of `CONFIG.image_widths`
+/// narrower than itself, writing the results next to the copied original.
+/// Results are cached in `CONFIG.image_cache` until the original changes.
+/// Returns `None` for remote images and formats that are left alone.
+pub fn process(url: &str) -> Result<Option<Image>, Box<dyn Error>> {
+    let (stem, extension) = match url.strip_prefix('/').and_then(|u| u.rsplit_once('.')) {
+        Some((stem, extension)) if !url.starts_with("//") => (stem, extension),
+        _ => return Ok(None),
+    };
+    if !FORMATS.contains(&extension.to_lowercase().as_str()) {
+        return Ok(None);
+    }
+
+    let source = format!("static_html/{}.{}", stem, extension);
+    if !Path::new(&source).is_file() {
+        return Err(format!("image {} not found in static_html", url).into());
+    }
+    let (width, height) = image::image_dimensions(&source)
+        .map_err(|e| format!("could not read image {}: {}", source, e))?;
+
+    let mut widths = CONFIG
+        .image_widths
+        .iter()
+        .copied()
+        .filter(|&w| w < width)
+        .collect::<Vec<_>>();
+    widths.sort_unstable();
+    widths.dedup();
+
+    let mut original = None;
+    let mut variant = |name: String, w: u32| -> Result<(String, u32), Box<dyn Error>> {
+        let cached = format!("{}/{}", CONFIG.image_cache, name);
+        if !is_fresh(&cached, &source) {
+            let image = match &original {
+                Some(image) => image,
+                None => original.insert(
+                    image::open(&source)
+                        .map_err(|e| format!("could not read image {}: {}", source, e))?,
+                ),
+            };
+            let h = ((height as f64) * (w as f64) / (width as f64))
+                .round()
+                .max(1.0) as u32;
+            let resized = image.resize_exact(w, h, FilterType::Lanczos3);
+            save(&resized, &cached)?;
+        }
+        fs::copy(&cached, format!("html/{}", name))?;
+        Ok((format!("/{}", name), w))
+    };
+
+    let mut variants = Vec::new();
+    for &w in widths.iter() {
+        variants.push(variant(format!("{}-{}w.{}", stem, w, extension), w)?);
+    }
+    variants.push((url.to_string(), width));
+
+    Ok(Some(Image {
+        url: url.to_string(),
+        width,
+        height,
+        variants,
+    }))
+}
+
+/// Whether a cached file exists and is newer than its source.
+fn is_fresh(cached: &str, source: &str) -> bool {
+    let modified = |path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(cached), modified(source)) {
+        (Some(cached), Some(source)) => cached >= source,
+        _ => false,
+    }
+}
+
+fn save(image: &DynamicImage, path: &str) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let format = ImageFormat::from_path(path)?;
+    // not every format supports every color type, e.g. jpeg has no alpha
+    let image = match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    };
+    image
+        .save_with_format(path, format)
+        .map_err(|e| format!("could not write image {}: {}", path, e))?;
+    Ok(())
+}
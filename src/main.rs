@@ -1,36 +1,76 @@
+// diesel 1.4's derives expand to impls nested inside of functions.
+#![allow(non_local_definitions)]
+
 use std::error::Error;
 use std::fs::File;
 use std::io::prelude::*;
 
 mod code;
 mod config;
+mod content;
 mod editing;
+mod images;
+mod math;
 mod models;
 mod org;
 mod polyring;
 mod render;
+mod revisions;
 mod rss;
 mod schema;
+mod shortcodes;
+mod snippets;
+mod templates;
 
 use models::Post;
 use org::*;
 
 #[macro_use]
 extern crate diesel;
+#[cfg(feature = "sqlite")]
+#[macro_use]
+extern crate diesel_migrations;
 
-use diesel::{pg::PgConnection, prelude::*};
+use diesel::prelude::*;
 use dotenv::dotenv;
 use std::env;
 
 type AResult<T> = Result<T, Box<dyn Error>>;
 
-pub fn establish_connection() -> PgConnection {
+#[cfg(not(feature = "sqlite"))]
+pub type DbConnection = diesel::pg::PgConnection;
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::sqlite::SqliteConnection;
+
+#[cfg(feature = "sqlite")]
+embed_migrations!("migrations.sqlite");
+
+#[cfg(not(feature = "sqlite"))]
+fn connect(db_url: &str) -> DbConnection {
+    if db_url.starts_with("sqlite://") {
+        panic!("DATABASE_URL is a sqlite url, build with `--features sqlite` to use SQLite.");
+    }
+    DbConnection::establish(db_url).unwrap_or_else(|_| panic!("Error connecting to {}", db_url))
+}
+
+#[cfg(feature = "sqlite")]
+fn connect(db_url: &str) -> DbConnection {
+    let path = db_url.trim_start_matches("sqlite://");
+    let db =
+        DbConnection::establish(path).unwrap_or_else(|_| panic!("Error connecting to {}", db_url));
+    db.execute("PRAGMA foreign_keys = ON")
+        .expect("Error enabling foreign keys");
+    embedded_migrations::run(&db).expect("Error running migrations");
+    db
+}
+
+pub fn establish_connection() -> DbConnection {
     dotenv().ok();
     let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set.");
-    PgConnection::establish(&db_url).expect(&format!("Error connecting to {}", db_url))
+    connect(&db_url)
 }
 
-fn list(filter: &str, db: &PgConnection) -> AResult<()> {
+fn list(filter: &str, db: &DbConnection) -> AResult<()> {
     use crate::schema::posts::dsl::*;
 
     let filter = format!("%{}%", filter);
@@ -43,7 +83,12 @@ fn list(filter: &str, db: &PgConnection) -> AResult<()> {
     println!("{:^25} | {:^10} | {:^50}", "URL", "PUBLISHED", "TITLE");
     println!("{:-^25}-+-{:-^10}-+-{:-^50}", "", "", "");
     for entry in entries.iter() {
-        let publ = entry.published.map(|_| "yes").unwrap_or("no");
+        let publ = match entry.published {
+            Some(_) if entry.is_published() && entry.unlisted => "unlisted",
+            Some(_) if entry.is_published() => "yes",
+            Some(_) => "scheduled",
+            None => "no",
+        };
         println!("{:^25} | {:^10} | {:^50}", entry.url, publ, entry.title);
     }
 
@@ -54,8 +99,7 @@ pub struct RenderConfig {
     preview: bool,
 }
 
-fn render_all(db: &PgConnection, config: &RenderConfig) -> AResult<()> {
-    use crate::schema::posts::dsl::*;
+fn render_all(content: &content::Content, config: &RenderConfig) -> AResult<()> {
     use fs_extra::dir::{copy, CopyOptions};
 
     std::fs::remove_dir_all("html").ok();
@@ -70,36 +114,100 @@ fn render_all(db: &PgConnection, config: &RenderConfig) -> AResult<()> {
     options.content_only = true;
     copy("static_html", "html", &options)?;
 
+    println!("rendering syntax.css.");
+    let css = code::stylesheet(&config::CONFIG.syntax_theme)?;
+    std::fs::write("html/syntax.css", css)?;
+
     println!("rendering rss.");
-    let rss = rss::create_feed(db)?;
+    let rss = rss::create_feed(content)?;
     std::fs::write("html/rss.xml", rss)?;
 
-    let pages = if config.preview {
-        posts.load::<Post>(db)?
-    } else {
-        posts.filter(published.is_not_null()).load::<Post>(db)?
-    };
+    let pages = content
+        .posts
+        .iter()
+        .filter(|p| config.preview || p.is_published());
 
-    for page in pages.iter() {
+    for page in pages {
         println!("rendering page {}.", page.url);
-        let rendered = render::blogpost(page, db)?;
+        let rendered = render::blogpost(page, content)?;
         std::fs::write(PageKind::Post.path_of(&page.url), rendered)?;
     }
 
-    let tags = {
-        use schema::tags_meta::dsl::*;
-        tags_meta.select(tag).load::<String>(db)?
-    };
-    for tag in tags.iter() {
-        println!("rendering tag {}.", tag);
-        let rendered = render::tag(&tag, db)?;
-        std::fs::write(PageKind::Tag.path_of(&tag), rendered)?;
+    for redirect in content.redirects.iter() {
+        // a new post may have taken over the old url
+        if content.posts.iter().any(|p| p.url == redirect.url) {
+            continue;
+        }
+        println!(
+            "rendering redirect {} -> {}.",
+            redirect.url, redirect.target
+        );
+        let rendered = render::redirect(redirect, content)?;
+        std::fs::write(PageKind::Post.path_of(&redirect.url), rendered)?;
+    }
+
+    if content.tag_meta("index").is_some() {
+        Err("a tag can't be named `index`, its page would replace the tag index.")?;
+    }
+    for meta in content.tags_meta.iter() {
+        println!("rendering tag {}.", meta.tag);
+        let pages = render::tag(&meta.tag, content)?;
+        write_listing(Listing::Tag(&meta.tag), pages)?;
+    }
+
+    println!("rendering tag index.");
+    let rendered = render::tag_overview(content)?;
+    std::fs::write(PageKind::Tag.path_of("index"), rendered)?;
+
+    println!("rendering archive.");
+    let rendered = render::archive_index(content)?;
+    std::fs::write(PageKind::Archive.path_of("index"), rendered)?;
+
+    for (year, months) in content.archive() {
+        println!("rendering archive {}.", year);
+        let rendered = render::archive_year(year, content)?;
+        std::fs::write(
+            PageKind::Archive.path_of(&archive_item(year, None)),
+            rendered,
+        )?;
+
+        std::fs::create_dir_all(format!("{}{}", PageKind::Archive.dir(), year))?;
+        for month in months.into_keys() {
+            let rendered = render::archive_month(year, month, content)?;
+            std::fs::write(
+                PageKind::Archive.path_of(&archive_item(year, Some(month))),
+                rendered,
+            )?;
+        }
+    }
+
+    for series in content.series.iter() {
+        println!("rendering series {}.", series.name);
+        let rendered = render::series(&series.name, content)?;
+        std::fs::write(PageKind::Series.path_of(&series.name), rendered)?;
+    }
+
+    for author in content.authors.iter() {
+        println!("rendering author {}.", author.name);
+        let rendered = render::author(&author.name, content)?;
+        std::fs::write(PageKind::Author.path_of(&author.name), rendered)?;
     }
 
     println!("rendering overview.");
-    let overview = render::overview(db, config)?;
-    std::fs::write("html/index.html", overview)?;
+    let overview = render::overview(content, config)?;
+    write_listing(Listing::Overview, overview)?;
+
+    Ok(())
+}
 
+fn write_listing(listing: Listing, pages: Vec<String>) -> AResult<()> {
+    for (i, page) in pages.into_iter().enumerate() {
+        let path = listing.page_path(i + 1);
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, page)?;
+    }
     Ok(())
 }
 
@@ -135,27 +243,85 @@ fn main() -> AResult<()> {
         opt list: Option<String>,   desc: "Display a list of recent posts.";
         opt render: bool,           desc: "Renders the website.";
         opt tag: Option<String>,    desc: "Edit the description of a tag.";
+        opt toggle_tag: Option<String>, desc: "Hide a tag from the tag index and post tag lists, or show it again.";
+        opt series: Option<String>, desc: "Edit the title and description of a series.";
+        opt author: Option<String>, desc: "Edit the name, email and description of an author.";
         opt send: bool,             desc: "Transfers the files to the server.";
         opt preview: bool,          desc: "Preview rendering: also renders unpublished posts";
+        opt content: Option<String>, desc: "Render from a directory of Markdown files instead of the database.";
+        opt export: Option<String>, desc: "Export all posts and tags to a directory.";
+        opt import: Option<String>, desc: "Import posts and tags from a directory written by --export.";
+        opt revisions: Option<String>, desc: "List the saved revisions of a post.";
+        opt diff: Vec<i32> = vec![], multi: true, desc: "Show the changes between two revisions.";
+        opt restore: Option<i32>,   desc: "Restore a post to an earlier revision.";
+        opt rename: Vec<String> = vec![], multi: true, desc: "Rename a post, the old url redirects to the new one.";
+        opt delete: Option<String>, desc: "Delete a post.";
+        opt prune_tags: bool,       desc: "Remove tags that aren't used by any post.";
     }
     .parse_or_exit();
 
-    let connection = establish_connection();
+    // only connect if a command actually needs the database
+    let connection = std::cell::OnceCell::new();
+    let db = || connection.get_or_init(establish_connection);
 
     if let Some(post) = args.edit {
-        editing::edit_post(&post, &connection)?;
+        editing::edit_post(&post, db())?;
+    }
+    if !args.rename.is_empty() {
+        match &args.rename[..] {
+            [old, new] => editing::rename_post(old, new, db())?,
+            _ => Err("--rename takes exactly two posts.")?,
+        }
+    }
+    if let Some(post) = args.delete {
+        editing::delete_post(&post, db())?;
+    }
+    if args.prune_tags {
+        editing::prune_tags(db())?;
     }
     if let Some(tag) = args.tag {
-        editing::edit_tag(&tag, &connection)?;
+        editing::edit_tag(&tag, db())?;
+    }
+    if let Some(tag) = args.toggle_tag {
+        editing::toggle_tag(&tag, db())?;
+    }
+    if let Some(series) = args.series {
+        editing::edit_series(&series, db())?;
+    }
+    if let Some(author) = args.author {
+        editing::edit_author(&author, db())?;
+    }
+    if let Some(dir) = args.import {
+        content::Content::import(&dir, db())?;
+    }
+    if let Some(dir) = args.export {
+        content::Content::load_from_db(db())?.write_to_dir(&dir)?;
+    }
+    if let Some(post) = args.revisions {
+        revisions::list(&post, db())?;
+    }
+    if !args.diff.is_empty() {
+        match args.diff[..] {
+            [from, to] => revisions::diff(from, to, db())?,
+            _ => Err("--diff takes exactly two revisions.")?,
+        }
+    }
+    if let Some(revision) = args.restore {
+        revisions::restore(revision, db())?;
     }
     if let Some(filter) = args.list {
-        list(&filter, &connection)?;
+        list(&filter, db())?;
     }
     if args.render {
         let config = RenderConfig {
             preview: args.preview,
         };
-        render_all(&connection, &config)?;
+        let mut content = match args.content {
+            Some(dir) => content::Content::load_from_dir(&dir)?,
+            None => content::Content::load_from_db(db())?,
+        };
+        content.count_words()?;
+        render_all(&content, &config)?;
     }
     if args.send && !args.preview {
         transfer()?;
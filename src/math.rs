@@ -0,0 +1,151 @@
+use crate::snippets::Snippets;
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+use std::{error::Error, ops::Range};
+
+/// Converts `$...$` (inline) and `$$...$$` (display) outside of the given code ranges to MathML,
+/// returns the markdown with placeholders for the formulas.
+pub fn extract(
+    markdown: &str,
+    code: &[Range<usize>],
+    snippets: &mut Snippets,
+) -> Result<String, Box<dyn Error>> {
+    let bytes = markdown.as_bytes();
+    let mut out = String::with_capacity(markdown.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(range) = code.iter().find(|r| r.contains(&i)) {
+            i = range.end;
+            continue;
+        }
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'$' => {
+                let display = markdown[i..].starts_with("$$");
+                let delimiter = if display { "$$" } else { "$" };
+                let start = i + delimiter.len();
+                match closing(markdown, start, display, code) {
+                    Some(end) => {
+                        let tex = &markdown[start..end];
+                        let style = if display {
+                            DisplayStyle::Block
+                        } else {
+                            DisplayStyle::Inline
+                        };
+                        let mathml = latex_to_mathml(tex, style)
+                            .map_err(|e| format!("cannot parse math `{}`: {}", tex.trim(), e))?;
+
+                        out += &markdown[copied..i];
+                        out += &snippets.add(mathml);
+                        i = end + delimiter.len();
+                        copied = i;
+                    }
+                    None => i = start,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    out += &markdown[copied..];
+
+    Ok(out)
+}
+
+/// Finds the end of a formula starting at `start`, formulas never span paragraphs or code.
+/// Like pandoc, inline math must not start or end with a space or be followed by a digit,
+/// so that `$5 and $10` stays text.
+fn closing(markdown: &str, start: usize, display: bool, code: &[Range<usize>]) -> Option<usize> {
+    let limit = code
+        .iter()
+        .map(|r| r.start)
+        .filter(|&s| s >= start)
+        .min()
+        .unwrap_or(markdown.len());
+    let rest = &markdown[start..limit];
+    let mut end = None;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '$' => {
+                end = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let end = end?;
+    let tex = &rest[..end];
+    if tex.trim().is_empty() || tex.contains("\n\n") {
+        return None;
+    }
+
+    if display {
+        return markdown[start + end..]
+            .starts_with("$$")
+            .then(|| start + end);
+    }
+
+    let after = markdown[start + end + 1..].chars().next();
+    if tex.starts_with(char::is_whitespace)
+        || tex.ends_with(char::is_whitespace)
+        || after.is_some_and(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(start + end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract_all(markdown: &str, code: &[Range<usize>]) -> String {
+        extract(markdown, code, &mut Snippets::default()).unwrap()
+    }
+
+    #[test]
+    fn extracts_inline_and_display_math() {
+        let out = extract_all("a $x^2$ b", &[]);
+        assert!(out.starts_with("a ") && out.ends_with(" b"));
+        assert!(!out.contains('$'));
+
+        let out = extract_all("$$\\sum_i i$$", &[]);
+        assert!(!out.contains('$'));
+    }
+
+    #[test]
+    fn prices_stay_text() {
+        assert_eq!(extract_all("$5 and $10", &[]), "$5 and $10");
+        assert_eq!(extract_all("costs $ 5 $ each", &[]), "costs $ 5 $ each");
+    }
+
+    #[test]
+    fn escaped_dollars_stay_text() {
+        assert_eq!(extract_all(r"\$x$", &[]), r"\$x$");
+        assert_eq!(closing(r"a\$b$", 0, false, &[]), Some(4));
+    }
+
+    #[test]
+    fn math_stops_at_code_and_paragraphs() {
+        assert_eq!(extract_all("`$x$`", &[Range { start: 0, end: 5 }]), "`$x$`");
+        assert_eq!(
+            closing("x `$` y$", 0, false, &[Range { start: 2, end: 5 }]),
+            None
+        );
+        assert_eq!(closing("x\n\ny$", 0, false, &[]), None);
+    }
+
+    #[test]
+    fn display_math_needs_two_dollars() {
+        assert_eq!(closing("x$", 0, true, &[]), None);
+        assert_eq!(closing("x$$", 0, true, &[]), Some(1));
+    }
+
+    #[test]
+    fn reports_invalid_math() {
+        let error = extract(r"$\frac{a$", &[], &mut Snippets::default()).unwrap_err();
+        assert!(error.to_string().starts_with("cannot parse math"));
+    }
+}
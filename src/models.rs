@@ -2,8 +2,9 @@ use super::schema::*;
 use super::*;
 use chrono::NaiveDateTime;
 use org::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Queryable, Insertable, Debug, PartialEq, Eq)]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Eq)]
 #[table_name = "posts"]
 pub struct Post {
     pub url: String,
@@ -13,6 +14,15 @@ pub struct Post {
     pub updated: NaiveDateTime,
     pub content: String,
     pub published: Option<NaiveDateTime>,
+    /// Rendered when published, but left out of all listings.
+    pub unlisted: bool,
+    pub series: Option<String>,
+    /// Position within the series, posts without one come last.
+    pub series_part: Option<i32>,
+    /// Whether to show a table of contents.
+    pub toc: bool,
+    /// Excerpt in markdown, overrides the part before `<!-- more -->`.
+    pub summary: Option<String>,
 }
 
 pub fn now() -> NaiveDateTime {
@@ -34,8 +44,80 @@ pub struct TagMeta {
     pub description: String,
 }
 
+#[derive(Queryable, Insertable, AsChangeset, Debug, PartialEq, Eq)]
+#[table_name = "series"]
+pub struct Series {
+    pub name: String,
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Queryable, Insertable, AsChangeset, Debug, PartialEq, Eq)]
+#[table_name = "authors"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct Author {
+    pub name: String,
+    pub display_name: String,
+    pub email: Option<String>,
+    pub description: String,
+}
+
+#[derive(Queryable, Insertable, Debug, PartialEq, Eq)]
+#[table_name = "post_authors"]
+pub struct PostAuthor {
+    pub url: String,
+    pub author: String,
+    /// Authors are credited in ascending order.
+    pub position: i32,
+}
+
+/// A post that was renamed from `url` to `target`.
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[table_name = "redirects"]
+pub struct Redirect {
+    pub url: String,
+    pub target: String,
+}
+
+#[derive(Queryable, Debug, PartialEq, Eq)]
+pub struct Revision {
+    pub id: i32,
+    pub url: String,
+    pub saved: NaiveDateTime,
+    pub snapshot: String,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "post_revisions"]
+pub struct NewRevision<'a> {
+    pub url: &'a str,
+    pub saved: NaiveDateTime,
+    pub snapshot: &'a str,
+}
+
 impl Post {
-    pub fn load_from_db(name: &str, db: &PgConnection) -> AResult<Self> {
+    /// Whether the post is published and its publishing date has passed.
+    pub fn is_published(&self) -> bool {
+        self.published.is_some_and(|date| date <= now())
+    }
+
+    /// The summary if there is one, otherwise everything before the `<!-- more -->` marker.
+    /// A marker in code is just text, it only counts where it is parsed as html.
+    pub fn excerpt(&self) -> Option<&str> {
+        const MORE: &str = "<!-- more -->";
+        self.summary.as_deref().or_else(|| {
+            crate::render::parser(&self.content)
+                .into_offset_iter()
+                .find_map(|(event, range)| match event {
+                    pulldown_cmark::Event::Html(html) => {
+                        html.find(MORE).map(|i| &self.content[..range.start + i])
+                    }
+                    _ => None,
+                })
+        })
+    }
+
+    pub fn load_from_db(name: &str, db: &DbConnection) -> AResult<Self> {
         use crate::schema::posts::dsl::*;
         if let Some(post) = posts
             .filter(url.eq(name))
@@ -50,6 +132,123 @@ impl Post {
     }
 }
 
+impl TagMeta {
+    pub fn write_to_db(&self, db: &DbConnection) -> AResult<()> {
+        use crate::schema::tags_meta::dsl::*;
+        use diesel::dsl::*;
+
+        // no `on_conflict` here, upserts are not portable across backends
+        let exists = tags_meta
+            .filter(tag.eq(&self.tag))
+            .count()
+            .get_result::<i64>(db)?
+            > 0;
+        if exists {
+            update(tags_meta.filter(tag.eq(&self.tag)))
+                .set(self)
+                .execute(db)?;
+        } else {
+            insert_into(tags_meta).values(self).execute(db)?;
+        }
+        Ok(())
+    }
+}
+
+impl Series {
+    pub fn write_to_db(&self, db: &DbConnection) -> AResult<()> {
+        use crate::schema::series::dsl::*;
+        use diesel::dsl::*;
+
+        let exists = series
+            .filter(name.eq(&self.name))
+            .count()
+            .get_result::<i64>(db)?
+            > 0;
+        if exists {
+            update(series.filter(name.eq(&self.name)))
+                .set(self)
+                .execute(db)?;
+        } else {
+            insert_into(series).values(self).execute(db)?;
+        }
+        Ok(())
+    }
+
+    /// Series are created implicitly by posts, with the name as a placeholder title.
+    pub fn create_if_missing(series_name: &str, db: &DbConnection) -> AResult<()> {
+        use crate::schema::series::dsl::*;
+        use diesel::dsl::*;
+
+        let exists = series
+            .filter(name.eq(series_name))
+            .count()
+            .get_result::<i64>(db)?
+            > 0;
+        if !exists {
+            insert_into(series)
+                .values(Self::new(series_name))
+                .execute(db)?;
+        }
+        Ok(())
+    }
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            title: name.into(),
+            description: String::new(),
+        }
+    }
+}
+
+impl Author {
+    pub fn write_to_db(&self, db: &DbConnection) -> AResult<()> {
+        use crate::schema::authors::dsl::*;
+        use diesel::dsl::*;
+
+        let exists = authors
+            .filter(name.eq(&self.name))
+            .count()
+            .get_result::<i64>(db)?
+            > 0;
+        if exists {
+            update(authors.filter(name.eq(&self.name)))
+                .set(self)
+                .execute(db)?;
+        } else {
+            insert_into(authors).values(self).execute(db)?;
+        }
+        Ok(())
+    }
+
+    /// Authors are created implicitly by posts, with the name as a placeholder display name.
+    pub fn create_if_missing(author_name: &str, db: &DbConnection) -> AResult<()> {
+        use crate::schema::authors::dsl::*;
+        use diesel::dsl::*;
+
+        let exists = authors
+            .filter(name.eq(author_name))
+            .count()
+            .get_result::<i64>(db)?
+            > 0;
+        if !exists {
+            insert_into(authors)
+                .values(Self::new(author_name))
+                .execute(db)?;
+        }
+        Ok(())
+    }
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            display_name: name.into(),
+            email: None,
+            description: String::new(),
+        }
+    }
+}
+
 impl Linkable for Post {
     fn link(&self) -> String {
         format!(
@@ -69,3 +268,33 @@ impl Linkable for Tag {
         )
     }
 }
+
+impl Linkable for TagMeta {
+    fn link(&self) -> String {
+        format!(
+            r#"<a href="{}">{}</a>"#,
+            PageKind::Tag.url_of(&self.tag),
+            self.tag.to_uppercase(),
+        )
+    }
+}
+
+impl Linkable for Series {
+    fn link(&self) -> String {
+        format!(
+            r#"<a href="{}">{}</a>"#,
+            PageKind::Series.url_of(&self.name),
+            self.title
+        )
+    }
+}
+
+impl Linkable for Author {
+    fn link(&self) -> String {
+        format!(
+            r#"<a href="{}">{}</a>"#,
+            PageKind::Author.url_of(&self.name),
+            self.display_name
+        )
+    }
+}
@@ -2,6 +2,9 @@
 pub enum PageKind {
     Post,
     Tag,
+    Series,
+    Author,
+    Archive,
 }
 
 impl PageKind {
@@ -9,6 +12,9 @@ impl PageKind {
         match self {
             Self::Post => "post",
             Self::Tag => "tag",
+            Self::Series => "series",
+            Self::Author => "author",
+            Self::Archive => "archive",
         }
     }
     pub fn url_of(&self, item: &str) -> String {
@@ -21,7 +27,46 @@ impl PageKind {
         format!("html/{}/", self.name())
     }
     pub fn kinds() -> Vec<Self> {
-        vec![Self::Post, Self::Tag]
+        vec![
+            Self::Post,
+            Self::Tag,
+            Self::Series,
+            Self::Author,
+            Self::Archive,
+        ]
+    }
+}
+
+/// The archive page of a year or of a month in it, e.g. `2021` or `2021/03`.
+pub fn archive_item(year: i32, month: Option<u32>) -> String {
+    match month {
+        Some(month) => format!("{}/{:02}", year, month),
+        None => year.to_string(),
+    }
+}
+
+/// A list of posts that is split into pages of `CONFIG.page_size`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Listing<'a> {
+    Overview,
+    Tag(&'a str),
+}
+
+impl Listing<'_> {
+    /// Pages count from 1, the first one keeps the url of the whole listing.
+    pub fn page_url(&self, page: usize) -> String {
+        match (self, page) {
+            (Self::Overview, 1) => "/".into(),
+            (Self::Overview, page) => format!("/page/{}.html", page),
+            (Self::Tag(tag), 1) => PageKind::Tag.url_of(tag),
+            (Self::Tag(tag), page) => format!("/tag/{}/page/{}.html", tag, page),
+        }
+    }
+    pub fn page_path(&self, page: usize) -> String {
+        match (self, page) {
+            (Self::Overview, 1) => "html/index.html".into(),
+            _ => format!("html{}", self.page_url(page)),
+        }
     }
 }
 
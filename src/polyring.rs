@@ -1,9 +1,10 @@
 use crate::config::CONFIG;
 use lazy_static::lazy_static;
-use serde::Deserialize;
-use serde_json;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
 
 #[derive(Clone, Deserialize, Debug)]
+#[allow(dead_code)]
 pub struct Member {
     pub title: String,
     pub url: String,
@@ -21,12 +22,25 @@ lazy_static! {
             .unwrap();
         serde_json::from_str(&response).expect("incorrect json format.")
     };
-    pub static ref BANNER: String = format!(
-        include_str!("polyring-banner.html"),
-        prev = prev_next().0,
-        next = prev_next().1,
-        member_count = MEMBERS.len(),
-    );
+}
+
+#[derive(Serialize)]
+struct Banner {
+    prev: String,
+    next: String,
+    member_count: usize,
+}
+
+pub fn banner() -> Result<String, Box<dyn Error>> {
+    let (prev, next) = prev_next();
+    crate::templates::render(
+        "polyring-banner.html",
+        &Banner {
+            prev,
+            next,
+            member_count: MEMBERS.len(),
+        },
+    )
 }
 
 fn prev_next() -> (String, String) {
@@ -1,45 +1,368 @@
 use super::*;
+use config::CONFIG;
+use content::Content;
 use org::*;
 use pulldown_cmark::*;
+use snippets::Snippets;
+use templates::{Link, Meta, Navigation, Page};
+
+/// GitHub flavored extensions: tables, footnotes, strikethrough and task lists.
+pub fn parser(markdown: &str) -> Parser<'_> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    Parser::new_ext(markdown, options)
+}
+
+/// Byte ranges of code and raw html, which shortcodes and math are not expanded in.
+fn code_ranges(markdown: &str) -> Vec<std::ops::Range<usize>> {
+    parser(markdown)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(_) | Event::Html(_) | Event::Start(Tag::CodeBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
 
-pub fn render_raw(post: &str) -> String {
-    let parser = Parser::new(post);
+/// Renders shortcodes and math ahead of the markdown, see `Snippets`.
+fn prerender(markdown: &str) -> AResult<(String, Snippets)> {
+    let mut snippets = Snippets::default();
+    let markdown = shortcodes::expand(markdown, &code_ranges(markdown), &mut snippets)?;
+    let markdown = math::extract(&markdown, &code_ranges(&markdown), &mut snippets)?;
+    Ok((markdown, snippets))
+}
+
+/// Plain html without highlighting, code blocks only keep the language of their info string.
+fn raw_html(post: &str) -> AResult<String> {
+    let (post, snippets) = prerender(post)?;
+    let mut events = Vec::new();
+    for event in parser(&post) {
+        events.push(match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let lang = code::CodeInfo::parse(&info)?.lang;
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang.into())))
+            }
+            event => event,
+        });
+    }
+    let events = footnotes(snippets.insert(events));
     let mut html = String::new();
-    html::push_html(&mut html, parser);
-    html
+    html::push_html(&mut html, events.into_iter());
+    Ok(html)
+}
+
+pub fn render_raw(url: &str, post: &str) -> AResult<String> {
+    raw_html(post).map_err(|e| format!("could not render {}: {}", url, e).into())
+}
+
+/// What to do with the headings of rendered markdown.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Headings {
+    /// Ids with anchors linking to them.
+    Anchors,
+    /// Anchors and a table of contents, see `heading_anchors`.
+    Contents,
+    /// Left alone, for parts of a page like excerpts, whose ids would collide.
+    Plain,
+}
+
+fn render_markdown(url: &str, post: &str, headings: Headings) -> AResult<String> {
+    let (post, snippets) =
+        prerender(post).map_err(|e| format!("could not render {}: {}", url, e))?;
+
+    let mut events = Vec::new();
+    let mut code_block = None;
+    let mut image = None;
+
+    use Event::*;
+    for event in parser(&post) {
+        match event {
+            Start(Tag::Image(kind, src, title)) => image = Some((kind, src, title, String::new())),
+            Text(text) | Code(text) if image.is_some() => image.as_mut().unwrap().3 += &text,
+            End(Tag::Image(..)) => {
+                let (kind, src, title, alt) = image.take().unwrap();
+                let processed = images::process(&src)
+                    .map_err(|e| format!("could not render {}: {}", url, e))?;
+                match processed {
+                    Some(processed) => {
+                        events.push(Html(resized_image(&processed, &alt, &title).into()))
+                    }
+                    None => events.extend(vec![
+                        Start(Tag::Image(kind, src.clone(), title.clone())),
+                        Text(alt.into()),
+                        End(Tag::Image(kind, src, title)),
+                    ]),
+                }
+            }
+            _ if image.is_some() => {}
+            Start(Tag::CodeBlock(kind)) => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => code::CodeInfo::parse(&info)
+                        .map_err(|e| format!("could not render {}: {}", url, e))?,
+                    CodeBlockKind::Indented => code::CodeInfo::parse("")?,
+                };
+                if info.syntax.is_none() && !info.lang.is_empty() {
+                    println!("warning: {} uses unknown language \"{}\".", url, info.lang);
+                }
+                code_block = Some((info, String::new()));
+            }
+            Text(text) => match code_block.as_mut() {
+                Some((_, code)) => code.push_str(&text),
+                None => events.push(Text(text)),
+            },
+            End(Tag::CodeBlock(_)) => {
+                if let Some((info, code)) = code_block.take() {
+                    let html = code::highlight(&code, &info)
+                        .map_err(|e| format!("could not render {}: {}", url, e))?;
+                    events.push(Html(html.into()));
+                }
+            }
+            event => events.push(event),
+        }
+    }
+
+    let events = snippets.insert(events);
+    let events = match headings {
+        Headings::Plain => footnotes(events),
+        headings => heading_anchors(footnotes(events), headings == Headings::Contents),
+    };
+
+    let mut html_out = String::new();
+    html::push_html(&mut html_out, events.into_iter());
+
+    Ok(html_out)
 }
 
-fn render_markdown(post: &str) -> String {
-    let parser = Parser::new(post);
+/// Lets the browser pick the smallest fitting variant.
+fn resized_image(image: &images::Image, alt: &str, title: &str) -> String {
+    let srcset = image
+        .variants
+        .iter()
+        .map(|(url, width)| format!("{} {}w", url, width))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sizes = "(max-width: 800px) 100vw, 800px";
+
+    let escape = |text: &str| {
+        let mut escaped = String::new();
+        escape::escape_html(&mut escaped, text).unwrap();
+        escaped
+    };
+    let title = match title {
+        "" => String::new(),
+        title => format!(r#" title="{}""#, escape(title)),
+    };
 
-    let mut code_lang = None;
+    format!(
+        r#"<img class="resized" src="{src}" srcset="{srcset}" sizes="{sizes}" width="{width}" height="{height}" alt="{alt}"{title} loading="lazy">"#,
+        sizes = sizes,
+        src = image.url,
+        srcset = srcset,
+        width = image.width,
+        height = image.height,
+        alt = escape(alt),
+        title = title,
+    )
+}
 
+/// Moves all footnote definitions into a list at the end, numbered in order of reference
+/// and linking back to where they are referenced first.
+fn footnotes(events: Vec<Event>) -> Vec<Event> {
     use Event::*;
-    let parser = parser.flat_map(|event| match &event {
-        Start(Tag::CodeBlock(kind)) => {
-            if let CodeBlockKind::Fenced(kind) = kind {
-                code_lang = Some(kind.clone().into_string());
+
+    let mut out = Vec::with_capacity(events.len());
+    let mut definitions = Vec::new();
+    let mut order = Vec::new();
+    let mut current = None;
+    for event in events.into_iter() {
+        match event {
+            Start(Tag::FootnoteDefinition(label)) => current = Some((label, Vec::new())),
+            End(Tag::FootnoteDefinition(_)) => definitions.extend(current.take()),
+            FootnoteReference(label) => {
+                let id = slugify(&label);
+                let html = match order.iter().position(|l| l == &label) {
+                    Some(n) => format!(
+                        r##"<sup class="footnote-reference"><a href="#fn-{}">{}</a></sup>"##,
+                        id,
+                        n + 1
+                    ),
+                    None => {
+                        order.push(label);
+                        format!(
+                            r##"<sup class="footnote-reference" id="fnref-{0}"><a href="#fn-{0}">{1}</a></sup>"##,
+                            id,
+                            order.len()
+                        )
+                    }
+                };
+                match current.as_mut() {
+                    Some((_, body)) => body.push(Html(html.into())),
+                    None => out.push(Html(html.into())),
+                }
             }
-            vec![Html(r#"<div class="code">"#.into()), event]
+            event => match current.as_mut() {
+                Some((_, body)) => body.push(event),
+                None => out.push(event),
+            },
         }
-        Text(code) => code_lang
-            .as_ref()
-            .map(|lang| code::highlight(&code, &lang).ok())
-            .flatten()
-            .map_or(vec![event], |html| vec![Html(html.into())]),
-        End(Tag::CodeBlock(_)) => {
-            code_lang = None;
-            vec![event, Html("</div>".into())]
+    }
+
+    if definitions.is_empty() {
+        return out;
+    }
+
+    // unreferenced footnotes go last
+    definitions
+        .sort_by_key(|(label, _)| order.iter().position(|l| l == label).unwrap_or(order.len()));
+
+    out.push(Html(r#"<section class="footnotes"><ol>"#.into()));
+    for (label, mut body) in definitions.into_iter() {
+        let id = slugify(&label);
+        out.push(Html(format!(r#"<li id="fn-{}">"#, id).into()));
+        if order.contains(&label) {
+            let backref = Html(
+                format!(
+                    r##" <a href="#fnref-{}" class="footnote-backref">↩</a>"##,
+                    id
+                )
+                .into(),
+            );
+            match body.last() {
+                Some(End(Tag::Paragraph)) => body.insert(body.len() - 1, backref),
+                _ => body.push(backref),
+            }
         }
-        _ => {
-            vec![event]
+        out.extend(body);
+        out.push(Html("</li>".into()));
+    }
+    out.push(Html("</ol></section>".into()));
+
+    out
+}
+
+/// Turns a heading into an id like `some-heading`.
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
         }
-    });
+    }
+    match slug.trim_matches('-') {
+        "" => String::from("section"),
+        slug => slug.to_string(),
+    }
+}
 
-    let mut html_out = String::new();
-    html::push_html(&mut html_out, parser);
+/// Gives every heading a unique id with a permalink anchor,
+/// and inserts the table of contents if `toc` is set.
+fn heading_anchors(events: Vec<Event>, toc: bool) -> Vec<Event> {
+    use Event::*;
+
+    let mut headings = Vec::new();
+    let mut ids = std::collections::HashSet::new();
+    let mut current = None;
+    for event in events.iter() {
+        match event {
+            Start(Tag::Heading(level)) => current = Some((*level, String::new())),
+            Text(text) | Code(text) => {
+                if let Some((_, heading)) = current.as_mut() {
+                    *heading += text;
+                }
+            }
+            End(Tag::Heading(_)) => {
+                let (level, text) = current.take().unwrap();
+                let slug = slugify(&text);
+                let mut id = slug.clone();
+                let mut n = 1;
+                while !ids.insert(id.clone()) {
+                    id = format!("{}-{}", slug, n);
+                    n += 1;
+                }
+                headings.push((level, id, text));
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = Vec::with_capacity(events.len());
+    let mut marker = false;
+    let mut heading = headings.iter();
+    let mut id = "";
+    let mut i = 0;
+    while i < events.len() {
+        match &events[i] {
+            Start(Tag::Heading(level)) => {
+                id = &heading.next().unwrap().1;
+                out.push(Html(format!(r#"<h{} id="{}">"#, level, id).into()));
+            }
+            End(Tag::Heading(level)) => {
+                out.push(Html(
+                    format!(r##"<a class="anchor" href="#{}">#</a></h{}>"##, id, level).into(),
+                ));
+            }
+            Start(Tag::Paragraph) if toc && !marker => {
+                // the marker may be split up into several text events
+                let end = events[i..]
+                    .iter()
+                    .position(|e| matches!(e, End(Tag::Paragraph)))
+                    .map_or(events.len(), |end| i + end);
+                let text = events[i + 1..end]
+                    .iter()
+                    .map(|e| match e {
+                        Text(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect::<Option<String>>();
+                if text.as_deref().map(str::trim) == Some("[TOC]") {
+                    out.push(Html(table_of_contents(&headings).into()));
+                    marker = true;
+                    i = end + 1;
+                    continue;
+                }
+                out.push(events[i].clone());
+            }
+            event => out.push(event.clone()),
+        }
+        i += 1;
+    }
 
-    html_out
+    if toc && !marker {
+        out.insert(0, Html(table_of_contents(&headings).into()));
+    }
+
+    out
+}
+
+/// Nested list of all headings below the title.
+fn table_of_contents(headings: &[(u32, String, String)]) -> String {
+    let mut html = String::from(r#"<nav class="toc"><strong>Contents</strong>"#);
+    let mut levels = Vec::new();
+    for (level, id, text) in headings.iter().filter(|h| h.0 > 1) {
+        while levels.last().is_some_and(|l| l > level) {
+            html += "</li></ul>";
+            levels.pop();
+        }
+        if levels.last() == Some(level) {
+            html += "</li>";
+        } else {
+            html += "<ul>";
+            levels.push(*level);
+        }
+        let mut text_html = String::new();
+        escape::escape_html(&mut text_html, text).unwrap();
+        html += &format!(r##"<li><a href="#{}">{}</a>"##, id, text_html);
+    }
+    for _ in levels {
+        html += "</li></ul>";
+    }
+    html += "</nav>";
+    html
 }
 
 use chrono::NaiveDateTime;
@@ -52,191 +375,623 @@ fn copyright_years(from: &NaiveDateTime, to: &NaiveDateTime) -> String {
     copyright
 }
 
-fn bottom_navigation(this: &Post, db: &PgConnection) -> AResult<String> {
-    use crate::schema::posts::dsl::*;
+fn bottom_navigation(this: &Post, content: &Content) -> AResult<Navigation> {
+    let listed = content.listed().collect::<Vec<_>>();
 
-    let publ = published.is_not_null();
+    let order = |p: &Post| (p.published, p.created);
+    let prev = listed.iter().rev().find(|p| order(p) < order(this));
+    let next = listed.iter().find(|p| order(p) > order(this));
+    let first = listed.first().ok_or("no published posts")?;
+    let last = listed.last().ok_or("no published posts")?;
+
+    let link = |title, linked: &Post| Link {
+        title,
+        url: PageKind::Post.url_of(&linked.url),
+    };
 
-    let prev = posts
-        .filter(created.lt(&this.created).and(publ))
-        .order_by(created.desc())
-        .limit(1)
-        .load::<Post>(db)?;
+    let (lname, llink) = match prev {
+        Some(prev) => ("Prev", prev),
+        None => ("Last", last),
+    };
 
-    let next = posts
-        .filter(created.gt(&this.created).and(publ))
-        .order_by(created.asc())
-        .limit(1)
-        .load::<Post>(db)?;
+    let (rname, rlink) = match next {
+        Some(next) => ("Next", next),
+        None => ("First", first),
+    };
 
-    let first = posts
-        .filter(publ)
-        .order_by(created.asc())
-        .limit(1)
-        .load::<Post>(db)?;
+    Ok(Navigation {
+        prev: Some(link(format!("← {}", lname), llink)),
+        next: Some(link(format!("{} →", rname), rlink)),
+    })
+}
 
-    let last = posts
-        .filter(publ)
-        .order_by(created.desc())
-        .limit(1)
-        .load::<Post>(db)?;
+/// Splits posts into pages of `CONFIG.page_size`, an empty listing still gets a page.
+fn paginate<'a, 'b>(posts: &'b [&'a Post]) -> Vec<&'b [&'a Post]> {
+    if posts.is_empty() {
+        return vec![posts];
+    }
+    posts.chunks(CONFIG.page_size.max(1)).collect()
+}
 
-    let link = |dir, linked: &Post| {
-        format!(
-            r#" <a href="{}" class="bottom-nav-button">{}</a> "#,
-            PageKind::Post.url_of(&linked.url),
-            dir
-        )
+/// Links to the neighbours of the `page`th of `count` pages, `None` if there is only one.
+fn pagination(listing: Listing, page: usize, count: usize) -> Option<Navigation> {
+    let link = |title: &str, page| Link {
+        title: title.into(),
+        url: listing.page_url(page),
     };
+    (count > 1).then(|| Navigation {
+        prev: (page > 1).then(|| link("← Newer", page - 1)),
+        next: (page < count).then(|| link("Older →", page + 1)),
+    })
+}
 
-    let (lname, llink) = match prev.first() {
-        Some(first) => ("Prev", first),
-        None => ("Last", last.first().unwrap()),
-    };
+fn page_title(title: &str, page: usize) -> String {
+    match page {
+        1 => title.into(),
+        page => format!("{} – page {}", title, page),
+    }
+}
 
-    let (rname, rlink) = match next.first() {
-        Some(first) => ("Next", first),
-        None => ("First", first.first().unwrap()),
+fn tag_links(post_url: &str, content: &Content) -> Vec<Link> {
+    content
+        .tags_of(post_url)
+        .filter(|t| content.is_displayed(&t.tag))
+        .map(|t| Link {
+            title: t.tag.to_uppercase(),
+            url: PageKind::Tag.url_of(&t.tag),
+        })
+        .collect()
+}
+
+fn series_box(this: &Post, content: &Content) -> AResult<String> {
+    let name = match &this.series {
+        Some(name) => name,
+        None => return Ok(String::new()),
     };
+    let series = content
+        .series(name)
+        .ok_or("not in series table, something is wrong with db...")?;
+
+    // unlisted posts and previews still show where they belong
+    let mut parts = content.series_parts(name);
+    if !parts.iter().any(|p| p.url == this.url) {
+        parts.push(this);
+        crate::content::sort_parts(&mut parts);
+    }
+    let index = parts.iter().position(|p| p.url == this.url).unwrap();
 
-    let mut links = String::new();
+    let mut html = format!(
+        r#"<div class="series"><strong>Part {} of {}</strong> of the series {}<ol>"#,
+        index + 1,
+        parts.len(),
+        series.link()
+    );
+    for part in parts.iter() {
+        if part.url == this.url {
+            html += &format!("<li>{}</li>", part.title);
+        } else {
+            html += &format!("<li>{}</li>", part.link());
+        }
+    }
+    html += "</ol></div>";
 
-    links += &link(format!("← {}", lname), llink);
-    links += &link(format!("{} →", rname), rlink);
+    Ok(html)
+}
 
-    Ok(links)
+/// Joins the items like `a, b and c`.
+fn enumerate(items: Vec<String>) -> String {
+    match items.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+fn author_names(post: &Post, content: &Content) -> String {
+    let authors = content.authors_of(&post.url);
+    if authors.is_empty() {
+        CONFIG.author.clone()
+    } else {
+        enumerate(authors.iter().map(|a| a.display_name.clone()).collect())
+    }
 }
 
-fn tag_list(post_url: &str, db: &PgConnection) -> AResult<String> {
-    use crate::schema::tags::dsl::*;
-    let tag_urls = tags
-        .filter(url.eq(post_url))
-        .load::<models::Tag>(db)?
-        .into_iter()
-        .map(|t| t.link())
-        .collect::<String>();
-    if tag_urls.len() == 0 {
-        Ok(String::new())
+fn byline(post: &Post, content: &Content) -> String {
+    let authors = content.authors_of(&post.url);
+    let names = if authors.is_empty() {
+        CONFIG.author.clone()
     } else {
-        Ok(format!("<br><strong>Tags:</strong> {}<br>", tag_urls))
+        enumerate(authors.iter().map(|a| a.link()).collect())
+    };
+    format!(r#"<p class="byline">by {}</p>"#, names)
+}
+
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Words outside of code blocks, shortcodes and math and the minutes it takes to read them.
+pub struct Statistics {
+    pub words: usize,
+    pub minutes: usize,
+}
+
+pub fn statistics(post: &Post) -> AResult<Statistics> {
+    let (markdown, snippets) = prerender(&post.content).map_err(|e| {
+        format!(
+            "could not render {}: {}",
+            PageKind::Post.url_of(&post.url),
+            e
+        )
+    })?;
+    let mut words = 0;
+    let mut in_code_block = false;
+    for event in snippets.insert(parser(&markdown).collect()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Text(text) | Event::Code(text) if !in_code_block => {
+                words += text.split_whitespace().count()
+            }
+            _ => {}
+        }
     }
+    Ok(Statistics {
+        words,
+        minutes: words.div_ceil(WORDS_PER_MINUTE).max(1),
+    })
 }
 
-pub fn blogpost(post: &Post, db: &PgConnection) -> AResult<String> {
-    let mut html = render_markdown(&post.content);
-    html += &tag_list(&post.url, db)?;
-    Ok(format!(
-        include_str!("skeleton.html"),
-        body = html,
-        title = post.title,
-        copyright = copyright_years(&post.created, &post.updated),
-        bottom_navigation = bottom_navigation(post, db)?,
-    ))
+fn format_date(date: &NaiveDateTime) -> String {
+    date.date().format("%d-%m-%Y").to_string()
 }
 
-fn tag_overview(db: &PgConnection) -> AResult<String> {
-    use crate::schema::tags::dsl::*;
+/// Byline, dates and reading time above a post.
+fn post_header(post: &Post, content: &Content) -> String {
+    let statistics = content.statistics_of(&post.url);
+
+    let mut details = vec![post.published.map_or("preview".to_string(), |p| {
+        format!("published {}", format_date(&p))
+    })];
+    if post
+        .published
+        .is_none_or(|p| p.date() != post.updated.date())
+    {
+        details.push(format!("updated {}", format_date(&post.updated)));
+    }
+    details.push(word_count(statistics.words));
+    details.push(format!("{} min read", statistics.minutes));
+
+    format!(
+        r#"<header class="post-header">{}<p class="post-details">{}</p></header>"#,
+        byline(post, content),
+        details.join(" · ")
+    )
+}
 
-    let t = tags
-        .distinct_on(tag)
-        .order_by(tag)
-        .load::<models::Tag>(db)?;
+fn word_count(count: usize) -> String {
+    match count {
+        1 => "1 word".into(),
+        count => format!("{} words", count),
+    }
+}
 
-    let mut buf = String::from("<h1>Posts sorted by tags</h1>");
+/// Search engines show about this many characters of a description.
+const DESCRIPTION_LENGTH: usize = 160;
+
+/// The plain text of the markdown for meta descriptions, shortened to `DESCRIPTION_LENGTH`.
+fn description(url: &str, markdown: &str) -> AResult<Option<String>> {
+    let (markdown, snippets) =
+        prerender(markdown).map_err(|e| format!("could not render {}: {}", url, e))?;
+    let mut text = String::new();
+    let mut in_code_block = false;
+    for event in snippets.insert(parser(&markdown).collect()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Text(_) if in_code_block => {}
+            Event::Text(t) | Event::Code(t) => text += &t,
+            Event::SoftBreak
+            | Event::HardBreak
+            | Event::End(Tag::Paragraph | Tag::Heading(_) | Tag::Item | Tag::TableCell) => {
+                text += " "
+            }
+            _ => {}
+        }
+    }
 
-    buf += "<ul>";
-    for t in t.into_iter() {
-        buf += &format!("<li>{}</li>", t.link());
+    let mut description = String::new();
+    for word in text.split_whitespace() {
+        if description.chars().count() + word.chars().count() >= DESCRIPTION_LENGTH {
+            description += "…";
+            break;
+        }
+        if !description.is_empty() {
+            description += " ";
+        }
+        description += word;
     }
-    buf += "</ul>";
+    Ok((!description.is_empty()).then_some(description))
+}
+
+/// The markdown without the `# Title` it starts with, which repeats the title of the post.
+fn without_title(markdown: &str) -> &str {
+    match parser(markdown).into_offset_iter().next() {
+        Some((Event::Start(Tag::Heading(1)), range)) => &markdown[range.end..],
+        _ => markdown,
+    }
+}
 
-    Ok(buf)
+fn absolute_url(url: &str) -> String {
+    format!("{}{}", CONFIG.url.trim_end_matches('/'), url)
 }
 
-pub fn overview(db: &PgConnection, config: &super::RenderConfig) -> AResult<String> {
-    let mut body = String::from("<h1>Blog Posts</h1>");
+pub fn blogpost(post: &Post, content: &Content) -> AResult<String> {
+    let mut html = post_header(post, content);
+    html += &series_box(post, content)?;
+    let headings = if post.toc {
+        Headings::Contents
+    } else {
+        Headings::Anchors
+    };
+    html += &render_markdown(&PageKind::Post.url_of(&post.url), &post.content, headings)?;
+
+    let mut page = Page::new(post.title.clone(), html);
+    page.author = author_names(post, content);
+    page.copyright = copyright_years(&post.created, &post.updated);
+    page.post = Some(post);
+    page.tags = tag_links(&post.url, content);
+    page.navigation = Some(bottom_navigation(post, content)?);
+    page.meta = Some(Meta {
+        description: description(
+            &PageKind::Post.url_of(&post.url),
+            without_title(post.excerpt().unwrap_or(&post.content)),
+        )?,
+        canonical: absolute_url(&PageKind::Post.url_of(&post.url)),
+        kind: "article",
+        published: post
+            .published
+            .map(|p| p.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+    });
+    templates::render("post.html", &page)
+}
+
+/// Lists the displayed tags with their post counts and the first line of their descriptions.
+pub fn tag_overview(content: &Content) -> AResult<String> {
+    let mut body = String::from("<h1>Posts sorted by tags</h1>");
+
+    body += "<hr>";
+    body += r#"<table class="post-list">"#;
+    body += "<th>Tag</th><th>Posts</th><th>Description</th>";
+    for meta in content.tags_meta.iter().filter(|m| m.display) {
+        let count = content
+            .listed()
+            .filter(|p| content.tags_of(&p.url).any(|t| t.tag == meta.tag))
+            .count();
+        let first_line = meta
+            .description
+            .lines()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or_default();
+        body += &format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            meta.link(),
+            count,
+            render_markdown(
+                &PageKind::Tag.url_of(&meta.tag),
+                first_line,
+                Headings::Plain
+            )?,
+        );
+    }
+    body += "</table><hr>";
 
-    use crate::schema::posts::dsl::*;
+    let mut page = Page::new("Tags".into(), body);
+    page.meta = Some(Meta {
+        description: None,
+        canonical: absolute_url(&PageKind::Tag.url_of("index")),
+        kind: "website",
+        published: None,
+    });
+    templates::render("page.html", &page)
+}
+
+/// Renders every page of the overview, see `Listing::Overview` for where they go.
+pub fn overview(content: &Content, config: &super::RenderConfig) -> AResult<Vec<String>> {
     let sites = if config.preview {
-        posts
-            .order_by(created.desc())
-            .select((title, url, published))
-            .load(db)
+        content.posts.iter().rev().collect::<Vec<_>>()
     } else {
-        posts
-            .filter(published.is_not_null())
-            .order_by(created.desc())
-            .select((title, url, published))
-            .load(db)
-    }?;
-
-    body += &create_table(&sites);
-    body += &crate::polyring::BANNER;
-
-    let dates = sites.into_iter().map(|p| p.2).flatten().collect::<Vec<_>>();
-
-    let page = format!(
-        include_str!("skeleton.html"),
-        title = "Overview",
-        body = body,
-        bottom_navigation = "",
-        copyright = copyright_years(&dates.last().unwrap(), &dates.first().unwrap())
-    );
+        content.listed().rev().collect::<Vec<_>>()
+    };
+
+    let dates = sites.iter().filter_map(|p| p.published).collect::<Vec<_>>();
+    let copyright = copyright_years(dates.last().unwrap(), dates.first().unwrap());
+    let banner = crate::polyring::banner()?;
 
-    Ok(page)
+    let pages = paginate(&sites);
+    let mut rendered = Vec::with_capacity(pages.len());
+    for (i, posts) in pages.iter().enumerate() {
+        let number = i + 1;
+        let mut body = String::from("<h1>Blog Posts</h1>");
+        body += &create_table(posts, content, true)?;
+        body += &format!(
+            r#"<p class="archive-link"><a href="{}">All posts by date →</a></p>"#,
+            PageKind::Archive.url_of("index")
+        );
+        body += &banner;
+
+        let mut page = Page::new(page_title("Overview", number), body);
+        page.copyright = copyright.clone();
+        page.navigation = pagination(Listing::Overview, number, pages.len());
+        page.meta = Some(Meta {
+            description: CONFIG.description.clone(),
+            canonical: absolute_url(&Listing::Overview.page_url(number)),
+            kind: "website",
+            published: None,
+        });
+        rendered.push(templates::render("page.html", &page)?);
+    }
+    Ok(rendered)
 }
 
-fn create_table(posts: &[(String, String, Option<NaiveDateTime>)]) -> String {
+/// Lists the posts, with their excerpts below if `excerpts` is set.
+fn create_table(posts: &[&Post], content: &Content, excerpts: bool) -> AResult<String> {
     let mut body = String::from("<hr>");
     body += r#"<table class="post-list">"#;
-    body += "<th>Post</th><th>Date</th>";
-    for (title, url, created) in posts.iter() {
+    body += "<th>Post</th><th>Date</th><th>Reading time</th>";
+    for post in posts.iter() {
+        let statistics = content.statistics_of(&post.url);
         body += &format!(
-            r#"<tr><td><a href="{}">{}</a></td><td>{}</td></tr>"#,
-            PageKind::Post.url_of(&url),
-            &title,
-            created.map_or("preview".to_string(), |c| c
-                .date()
-                .format("%d-%m-%Y")
-                .to_string())
+            r#"<tr><td><a href="{}">{}</a></td><td>{}</td><td title="{}">{} min</td></tr>"#,
+            PageKind::Post.url_of(&post.url),
+            &post.title,
+            post.published
+                .map_or("preview".to_string(), |p| format_date(&p)),
+            word_count(statistics.words),
+            statistics.minutes,
         );
+        if let Some(excerpt) = post.excerpt().filter(|_| excerpts) {
+            let url = PageKind::Post.url_of(&post.url);
+            body += &format!(
+                r#"<tr class="excerpt"><td colspan="3">{}<a href="{}">Read more →</a></td></tr>"#,
+                render_markdown(&url, without_title(excerpt), Headings::Plain)?,
+                url
+            );
+        }
     }
     body += "</table><hr>";
-    body
+    Ok(body)
+}
+
+#[derive(serde::Serialize)]
+struct RedirectPage<'a> {
+    title: &'a str,
+    url: String,
+    /// The absolute url of the target.
+    canonical: String,
 }
 
-pub fn tag(name: &str, db: &PgConnection) -> AResult<String> {
+pub fn redirect(redirect: &models::Redirect, content: &Content) -> AResult<String> {
+    let title = content
+        .posts
+        .iter()
+        .find(|p| p.url == redirect.target)
+        .map_or(redirect.target.as_str(), |p| p.title.as_str());
+    templates::render(
+        "redirect.html",
+        &RedirectPage {
+            title,
+            url: PageKind::Post.url_of(&redirect.target),
+            canonical: absolute_url(&PageKind::Post.url_of(&redirect.target)),
+        },
+    )
+}
+
+/// Renders every page of a tag, see `Listing::Tag` for where they go.
+pub fn tag(name: &str, content: &Content) -> AResult<Vec<String>> {
     let title = format!("Posts with tag {}", name.to_uppercase());
+
+    let sites = content
+        .listed()
+        .rev()
+        .filter(|p| content.tags_of(&p.url).any(|t| t.tag == name))
+        .collect::<Vec<_>>();
+
+    let description = &content
+        .tag_meta(name)
+        .ok_or("not in meta table, something is wrong with db...")?
+        .description;
+
+    let listing = Listing::Tag(name);
+    let meta_description = self::description(&PageKind::Tag.url_of(name), description)?;
+
+    let pages = paginate(&sites);
+    let mut rendered = Vec::with_capacity(pages.len());
+    for (i, posts) in pages.iter().enumerate() {
+        let number = i + 1;
+        let mut body = format!("<h1>{}</h1>", title);
+        if number == 1 {
+            body += &render_markdown(&PageKind::Tag.url_of(name), description, Headings::Anchors)?;
+        }
+        body += &create_table(posts, content, false)?;
+
+        let mut page = Page::new(page_title(&title, number), body);
+        page.navigation = pagination(listing, number, pages.len());
+        page.meta = Some(Meta {
+            description: meta_description.clone(),
+            canonical: absolute_url(&listing.page_url(number)),
+            kind: "website",
+            published: None,
+        });
+        rendered.push(templates::render("page.html", &page)?);
+    }
+    Ok(rendered)
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+fn post_count(count: usize) -> String {
+    match count {
+        1 => "1 post".into(),
+        count => format!("{} posts", count),
+    }
+}
+
+fn archive_page(item: &str, title: String, body: String) -> AResult<String> {
+    let mut page = Page::new(title, body);
+    page.meta = Some(Meta {
+        description: None,
+        canonical: absolute_url(&PageKind::Archive.url_of(item)),
+        kind: "website",
+        published: None,
+    });
+    templates::render("page.html", &page)
+}
+
+/// Links the years, newest first, with their post counts.
+pub fn archive_index(content: &Content) -> AResult<String> {
+    let mut body = String::from("<h1>Archive</h1>");
+    body += r#"<ul class="archive">"#;
+    for (year, months) in content.archive().iter().rev() {
+        body += &format!(
+            r#"<li><a href="{}">{}</a> ({})</li>"#,
+            PageKind::Archive.url_of(&archive_item(*year, None)),
+            year,
+            post_count(months.values().map(Vec::len).sum()),
+        );
+    }
+    body += "</ul>";
+
+    archive_page("index", "Archive".into(), body)
+}
+
+/// Links the months of the year and lists its posts, newest first.
+pub fn archive_year(year: i32, content: &Content) -> AResult<String> {
+    let archive = content.archive();
+    let months = archive.get(&year).ok_or("no posts in this year")?;
+
+    let title = format!("Posts from {}", year);
     let mut body = format!("<h1>{}</h1>", title);
+    body += r#"<ul class="archive">"#;
+    for (month, posts) in months.iter().rev() {
+        body += &format!(
+            r#"<li><a href="{}">{}</a> ({})</li>"#,
+            PageKind::Archive.url_of(&archive_item(year, Some(*month))),
+            MONTHS[*month as usize - 1],
+            post_count(posts.len()),
+        );
+    }
+    body += "</ul>";
 
-    use crate::schema::posts::dsl as p;
-    use crate::schema::tags::dsl as t;
-    use crate::schema::tags_meta::dsl as m;
-    let sites = t::tags
-        .inner_join(p::posts.on(p::url.eq(t::url)))
-        .filter(p::published.is_not_null())
-        .filter(t::tag.eq(name))
-        .order_by(p::created.desc())
-        .select((p::title, p::url, p::published))
-        .load(db)?;
-
-    let description = m::tags_meta
-        .filter(m::tag.eq(name))
-        .select(m::description)
-        .load::<String>(db)?;
-    let description = description
-        .first()
-        .ok_or("not in meta table, something is wrong with db...")?;
-
-    body += &render_markdown(&description);
-    body += &create_table(&sites);
-
-    let page = format!(
-        include_str!("skeleton.html"),
-        title = title,
-        body = body,
-        bottom_navigation = "",
-        copyright = ""
+    let posts = months.values().flatten().rev().copied().collect::<Vec<_>>();
+    body += &create_table(&posts, content, false)?;
+
+    archive_page(&archive_item(year, None), title, body)
+}
+
+pub fn archive_month(year: i32, month: u32, content: &Content) -> AResult<String> {
+    let archive = content.archive();
+    let posts = archive
+        .get(&year)
+        .and_then(|months| months.get(&month))
+        .ok_or("no posts in this month")?;
+
+    let title = format!("Posts from {} {}", MONTHS[month as usize - 1], year);
+    let mut body = format!("<h1>{}</h1>", title);
+    body += &format!(
+        r#"<p><a href="{}">← All of {}</a></p>"#,
+        PageKind::Archive.url_of(&archive_item(year, None)),
+        year
     );
-    Ok(page)
+    body += &create_table(
+        &posts.iter().rev().copied().collect::<Vec<_>>(),
+        content,
+        false,
+    )?;
+
+    archive_page(&archive_item(year, Some(month)), title, body)
+}
+
+pub fn series(name: &str, content: &Content) -> AResult<String> {
+    let series = content
+        .series(name)
+        .ok_or("not in series table, something is wrong with db...")?;
+    let mut body = format!("<h1>{}</h1>", series.title);
+
+    body += &render_markdown(
+        &PageKind::Series.url_of(name),
+        &series.description,
+        Headings::Anchors,
+    )?;
+    body += &create_table(&content.series_parts(name), content, false)?;
+
+    templates::render("page.html", &Page::new(series.title.clone(), body))
+}
+
+pub fn author(name: &str, content: &Content) -> AResult<String> {
+    let author = content
+        .author(name)
+        .ok_or("not in author table, something is wrong with db...")?;
+    let title = format!("Posts by {}", author.display_name);
+    let mut body = format!("<h1>{}</h1>", title);
+
+    let sites = content
+        .listed()
+        .rev()
+        .filter(|p| content.authors_of(&p.url).iter().any(|a| a.name == name))
+        .collect::<Vec<_>>();
+
+    body += &render_markdown(
+        &PageKind::Author.url_of(name),
+        &author.description,
+        Headings::Anchors,
+    )?;
+    body += &create_table(&sites, content, false)?;
+
+    let mut page = Page::new(title, body);
+    page.author = author.display_name.clone();
+    templates::render("page.html", &page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugifies_headings() {
+        assert_eq!(slugify("Hello World"), "hello-world");
+        assert_eq!(slugify("  What's new in 2.0?  "), "whats-new-in-20");
+        assert_eq!(slugify("a - b__c"), "a-b-c");
+        assert_eq!(slugify("Grüße"), "grüße");
+        assert_eq!(slugify("???"), "section");
+    }
+
+    #[test]
+    fn numbers_duplicate_anchors() {
+        let events = heading_anchors(parser("# Intro\n\n# Intro\n\n# Intro").collect(), false);
+        let mut html = String::new();
+        html::push_html(&mut html, events.into_iter());
+        for id in ["intro", "intro-1", "intro-2"] {
+            assert!(html.contains(&format!(r#"id="{}""#, id)), "{}", html);
+        }
+    }
+
+    #[test]
+    fn strips_the_title() {
+        assert_eq!(without_title("# Title\n\nText"), "\nText");
+        assert_eq!(without_title("\n# Title\nText"), "Text");
+        assert_eq!(without_title("Text\n\n# Title"), "Text\n\n# Title");
+        assert_eq!(without_title("## Part\n\nText"), "## Part\n\nText");
+    }
 }
@@ -0,0 +1,104 @@
+use super::*;
+use models::{NewRevision, Revision};
+
+/// Saves the new version of a post, has to be called before the old version is overwritten:
+/// posts without any revisions yet (i.e. from before revisions existed) get it saved as well.
+pub fn snapshot(name: &str, post: &editing::Post, db: &DbConnection) -> AResult<()> {
+    if !has_revisions(name, db)? {
+        if let Ok(current) = editing::Post::new_from_db(name, db) {
+            insert(name, &current, db)?;
+        }
+    }
+    insert(name, post, db)
+}
+
+/// Saves the current version of a post, so it can still be restored after it is deleted.
+pub fn save_current(name: &str, db: &DbConnection) -> AResult<()> {
+    let current = editing::Post::new_from_db(name, db)?;
+    insert(name, &current, db)
+}
+
+/// Stores a post in the same format the editor shows it.
+fn insert(name: &str, post: &editing::Post, db: &DbConnection) -> AResult<()> {
+    use crate::schema::post_revisions::dsl::*;
+
+    let text = editing::join_frontmatter(&post.meta, &post.content)?;
+    diesel::insert_into(post_revisions)
+        .values(NewRevision {
+            url: name,
+            saved: models::now(),
+            snapshot: &text,
+        })
+        .execute(db)?;
+    Ok(())
+}
+
+pub fn has_revisions(name: &str, db: &DbConnection) -> AResult<bool> {
+    use crate::schema::post_revisions::dsl::*;
+
+    let count = post_revisions
+        .filter(url.eq(name))
+        .count()
+        .get_result::<i64>(db)?;
+    Ok(count > 0)
+}
+
+fn load(revision: i32, db: &DbConnection) -> AResult<Revision> {
+    use crate::schema::post_revisions::dsl::*;
+
+    post_revisions
+        .filter(id.eq(revision))
+        .load::<Revision>(db)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("no revision with id `{}`.", revision).into())
+}
+
+pub fn list(name: &str, db: &DbConnection) -> AResult<()> {
+    use crate::schema::post_revisions::dsl::*;
+
+    let revisions = post_revisions
+        .filter(url.eq(name))
+        .order_by((saved.desc(), id.desc()))
+        .load::<Revision>(db)?;
+
+    println!("{:^8} | {:^20} | {:^50}", "ID", "SAVED", "TITLE");
+    println!("{:-^8}-+-{:-^20}-+-{:-^50}", "", "", "");
+    for revision in revisions.iter() {
+        let (meta, _) = editing::split_frontmatter::<editing::PostMeta>(&revision.snapshot)?;
+        let date = revision.saved.format("%d-%m-%Y %H:%M").to_string();
+        println!("{:^8} | {:^20} | {:^50}", revision.id, date, meta.title);
+    }
+
+    Ok(())
+}
+
+pub fn diff(from: i32, to: i32, db: &DbConnection) -> AResult<()> {
+    let from = load(from, db)?;
+    let to = load(to, db)?;
+
+    let diff = similar::TextDiff::from_lines(&from.snapshot, &to.snapshot);
+    print!(
+        "{}",
+        diff.unified_diff().header(
+            &format!("{} (revision {})", from.url, from.id),
+            &format!("{} (revision {})", to.url, to.id),
+        )
+    );
+
+    Ok(())
+}
+
+/// Writes an old revision back to its post, which in turn becomes the newest revision.
+pub fn restore(revision: i32, db: &DbConnection) -> AResult<()> {
+    let revision = load(revision, db)?;
+    let (meta, content) = editing::split_frontmatter(&revision.snapshot)?;
+
+    db.transaction(|| editing::Post { meta, content }.write_to_db(&revision.url, db))?;
+    println!(
+        "restored post {} to revision {}.",
+        revision.url, revision.id
+    );
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use super::*;
-use crate::schema::posts::dsl::{created, posts, published};
+use config::{FeedContent, CONFIG};
+use content::Content;
 use org::*;
 use quick_xml::se::to_string;
 use serde::Serialize;
@@ -38,32 +39,64 @@ struct Item {
     #[serde(rename = "pubDate")]
     pub_date: BString,
     description: BString,
+    /// RSS only allows a single author with an email address.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<BString>,
+    #[serde(rename = "dc:creator")]
+    creators: Vec<BString>,
 }
 
-pub fn create_feed(db: &PgConnection) -> AResult<String> {
+pub fn create_feed(content: &Content) -> AResult<String> {
     let url = "https://pluto.dev";
-    let items = posts
-        .filter(published.is_not_null())
-        .order_by(created.desc())
-        .limit(20)
-        .load::<Post>(db)?
-        .into_iter()
+    let items = content
+        .listed()
+        .rev()
+        .take(20)
         .map(|item| {
             let link = BString::from(format!("{}{}", url, PageKind::Post.url_of(&item.url)));
-            let pub_date = format!("{}", item.created.format("%a, %d %b %Y %H:%M:%S")).into();
-            let description = crate::render::render_raw(&item.content).into();
-            Item {
-                title: item.title.into(),
+            let published = item.published.unwrap_or(item.created);
+            let pub_date = format!("{}", published.format("%a, %d %b %Y %H:%M:%S")).into();
+            let page = PageKind::Post.url_of(&item.url);
+            let description = match item.excerpt() {
+                Some(excerpt) if CONFIG.feed_content == FeedContent::Excerpt => format!(
+                    r#"{}<p><a href="{}">Read more</a></p>"#,
+                    crate::render::render_raw(&page, excerpt)?,
+                    link.0
+                ),
+                _ => crate::render::render_raw(&page, &item.content)?,
+            }
+            .into();
+            let authors = content.authors_of(&item.url);
+            let author = authors
+                .iter()
+                .find_map(|a| {
+                    a.email
+                        .as_ref()
+                        .map(|e| format!("{} ({})", e, a.display_name))
+                })
+                .map(BString::from);
+            let creators = if authors.is_empty() {
+                vec![CONFIG.author.as_str().into()]
+            } else {
+                authors
+                    .iter()
+                    .map(|a| a.display_name.as_str().into())
+                    .collect()
+            };
+            Ok(Item {
+                title: item.title.as_str().into(),
                 guid: link.clone(),
                 link,
                 pub_date,
                 description,
-            }
+                author,
+                creators,
+            })
         })
-        .collect();
+        .collect::<AResult<_>>()?;
 
     let channel = Channel {
-        title: "Jonas' personal website".into(),
+        title: CONFIG.title.as_str().into(),
         link: url.into(),
         description: "Technology and Coding blog".into(),
         items,
@@ -72,7 +105,7 @@ pub fn create_feed(db: &PgConnection) -> AResult<String> {
     let xml = to_string(&channel)?;
 
     let rss = format!(
-        r#"<?xml version="1.0" encoding="UTF-8" ?><rss version="2.0">{}</rss> "#,
+        r#"<?xml version="1.0" encoding="UTF-8" ?><rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/">{}</rss> "#,
         xml
     );
 
@@ -1,3 +1,29 @@
+table! {
+    authors (name) {
+        name -> Varchar,
+        display_name -> Varchar,
+        email -> Nullable<Varchar>,
+        description -> Text,
+    }
+}
+
+table! {
+    post_authors (url, author) {
+        url -> Varchar,
+        author -> Varchar,
+        position -> Int4,
+    }
+}
+
+table! {
+    post_revisions (id) {
+        id -> Int4,
+        url -> Varchar,
+        saved -> Timestamp,
+        snapshot -> Text,
+    }
+}
+
 table! {
     posts (url) {
         url -> Varchar,
@@ -7,6 +33,26 @@ table! {
         updated -> Timestamp,
         content -> Text,
         published -> Nullable<Timestamp>,
+        unlisted -> Bool,
+        series -> Nullable<Varchar>,
+        series_part -> Nullable<Int4>,
+        toc -> Bool,
+        summary -> Nullable<Text>,
+    }
+}
+
+table! {
+    series (name) {
+        name -> Varchar,
+        title -> Varchar,
+        description -> Text,
+    }
+}
+
+table! {
+    redirects (url) {
+        url -> Varchar,
+        target -> Varchar,
     }
 }
 
@@ -25,10 +71,17 @@ table! {
     }
 }
 
+joinable!(post_authors -> authors (author));
+joinable!(posts -> series (series));
 joinable!(tags -> tags_meta (tag));
 
 allow_tables_to_appear_in_same_query!(
+    authors,
+    post_authors,
+    post_revisions,
     posts,
+    redirects,
+    series,
     tags,
     tags_meta,
 );
@@ -0,0 +1,329 @@
+use crate::config::CONFIG;
+use crate::snippets::Snippets;
+use pulldown_cmark::escape::escape_html;
+use std::{collections::HashMap, error::Error, ops::Range};
+
+/// `name(key="value", ...)`, the part shared by both kinds of shortcodes.
+struct Call<'a> {
+    name: &'a str,
+    args: HashMap<&'a str, String>,
+}
+
+/// Expands `{{ name(key="value") }}` and `{% name(key="value") %}body{% end %}` outside of
+/// the given code ranges with the templates in `CONFIG.shortcode_dir`, returns the markdown
+/// with placeholders for the expanded shortcodes. The body stays markdown between the
+/// placeholders for the html around it, so it is rendered like the rest of the page.
+pub fn expand(
+    markdown: &str,
+    code: &[Range<usize>],
+    snippets: &mut Snippets,
+) -> Result<String, Box<dyn Error>> {
+    let mut out = String::with_capacity(markdown.len());
+    // where the `{% end %}` of each open body starts and ends, and what replaces it
+    let mut open: Vec<(usize, usize, String)> = Vec::new();
+    let mut copied = 0;
+    let mut i = 0;
+    while i < markdown.len() {
+        if open.last().is_some_and(|(start, _, _)| *start == i) {
+            let (_, end, after) = open.pop().unwrap();
+            out += &markdown[copied..i];
+            out += &after;
+            i = end;
+            copied = i;
+            continue;
+        }
+        if let Some(range) = code.iter().find(|r| r.contains(&i)) {
+            i = range.end;
+            continue;
+        }
+
+        let rest = &markdown[i..];
+        if let Some((call, len)) = rest.strip_prefix("{{").and_then(|r| parse_call(r, "}}")) {
+            let (html, _) = render(&call, false).map_err(|e| at(markdown, i, call.name, &e))?;
+            out += &markdown[copied..i];
+            out += &snippets.add(html);
+            i += len + 2;
+            copied = i;
+        } else if let Some((call, len)) = rest.strip_prefix("{%").and_then(|r| parse_call(r, "%}"))
+        {
+            let start = i + len + 2;
+            let (body, end) = block_body(markdown, start, code)
+                .ok_or_else(|| at(markdown, i, call.name, "missing `{% end %}`"))?;
+            let (before, after) =
+                render(&call, true).map_err(|e| at(markdown, i, call.name, &e))?;
+
+            out += &markdown[copied..i];
+            out += &snippets.add(before);
+            match after {
+                Some(after) => {
+                    // a body over several lines holds blocks, which need their own paragraphs
+                    let separator = if markdown[start..body].contains('\n') {
+                        "\n\n"
+                    } else {
+                        ""
+                    };
+                    out += separator;
+                    open.push((body, end, format!("{}{}", separator, snippets.add(after))));
+                    i = start;
+                }
+                None => i = end,
+            }
+            copied = i;
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    out += &markdown[copied..];
+
+    Ok(out)
+}
+
+/// Posts and descriptions with frontmatter are stored starting with the rest of the line
+/// of its closing `---`, which doesn't count as a line of the body.
+fn at(markdown: &str, index: usize, name: &str, error: &str) -> String {
+    let mut line = markdown[..index].matches('\n').count() + 1;
+    if markdown.starts_with('\n') {
+        line -= 1;
+    }
+    format!("body line {}: shortcode `{}`: {}", line, name, error)
+}
+
+/// Parses a call followed by `close`, returns it and the length parsed.
+/// Anything that doesn't look like a call is not a shortcode and left alone.
+fn parse_call<'a>(text: &'a str, close: &str) -> Option<(Call<'a>, usize)> {
+    let mut rest = text.trim_start();
+    let name = identifier(&mut rest)?;
+    rest = rest.trim_start().strip_prefix('(')?;
+
+    let mut args = HashMap::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix(')') {
+            rest = r;
+            break;
+        }
+        let key = identifier(&mut rest)?;
+        rest = rest.trim_start().strip_prefix('=')?.trim_start();
+        args.insert(key, value(&mut rest)?);
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix(',') {
+            rest = r;
+        } else if !rest.starts_with(')') {
+            return None;
+        }
+    }
+
+    rest = rest.trim_start().strip_prefix(close)?;
+    Some((Call { name, args }, text.len() - rest.len()))
+}
+
+fn identifier<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    let end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(rest.len());
+    let (identifier, r) = rest.split_at(end);
+    *rest = r;
+    (!identifier.is_empty()).then_some(identifier)
+}
+
+/// Either a quoted string with `\"` and `\\` escapes or a bare word like `300`.
+fn value(rest: &mut &str) -> Option<String> {
+    let quoted = match rest.strip_prefix('"') {
+        Some(quoted) => quoted,
+        None => {
+            let end = rest
+                .find(|c: char| c == ',' || c == ')' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let (value, r) = rest.split_at(end);
+            *rest = r;
+            return (!value.is_empty()).then(|| value.to_string());
+        }
+    };
+
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                *rest = &quoted[i + 1..];
+                return Some(value);
+            }
+            '\\' => value.push(chars.next()?.1),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Finds the `{% end %}` matching a body starting at `start`, skipping code, returns
+/// where the end starts and where it ends.
+fn block_body(markdown: &str, start: usize, code: &[Range<usize>]) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut i = start;
+    while let Some(tag) = markdown[i..].find("{%").map(|s| s + i) {
+        i = tag + 2;
+        if code.iter().any(|r| r.contains(&tag)) {
+            continue;
+        }
+        let inner = &markdown[i..];
+        let end = inner.trim_start().strip_prefix("end").and_then(|r| {
+            let r = r.trim_start().strip_prefix("%}")?;
+            Some(inner.len() - r.len())
+        });
+        match end {
+            Some(_) if depth > 0 => depth -= 1,
+            Some(end) => return Some((tag, i + end)),
+            None if parse_call(inner, "%}").is_some() => depth += 1,
+            None => {}
+        }
+    }
+    None
+}
+
+/// Reads the template and fills it in, see `fill`.
+fn render(call: &Call, has_body: bool) -> Result<(String, Option<String>), String> {
+    let path = format!("{}/{}.html", CONFIG.shortcode_dir, call.name);
+    let template = std::fs::read_to_string(&path)
+        .map_err(|_| format!("not found in {}/", CONFIG.shortcode_dir))?;
+    fill(&template, &path, call, has_body)
+}
+
+/// Fills in `{{ key }}` with the escaped argument, `{{ key? }}` may be left out. Returns the
+/// html before `{{ body }}` and, if the template has one, the html after it. `{{ body? }}`
+/// may be left out as well.
+fn fill(
+    template: &str,
+    path: &str,
+    call: &Call,
+    has_body: bool,
+) -> Result<(String, Option<String>), String> {
+    let mut html = String::with_capacity(template.len());
+    let mut before = None;
+    let mut rest = template.trim_end();
+    while let Some((text, after)) = rest.split_once("{{") {
+        let (key, after) = after
+            .split_once("}}")
+            .ok_or(format!("unclosed `{{{{` in {}", path))?;
+        html += text;
+
+        let key = key.trim();
+        match (key, key.strip_suffix('?')) {
+            ("body", _) | (_, Some("body")) if before.is_some() => {
+                Err(format!("`{{{{ body }}}}` is used twice in {}", path))?
+            }
+            ("body", _) if !has_body => Err("needs a body, use `{% ... %}` and `{% end %}`")?,
+            ("body", _) | (_, Some("body")) if has_body => before = Some(std::mem::take(&mut html)),
+            (_, Some("body")) => {}
+            (_, Some(key)) => {
+                if let Some(value) = call.args.get(key) {
+                    escape_html(&mut html, value).unwrap();
+                }
+            }
+            (key, None) => {
+                let value = call
+                    .args
+                    .get(key)
+                    .ok_or(format!("missing argument `{}`", key))?;
+                escape_html(&mut html, value).unwrap();
+            }
+        }
+        rest = after;
+    }
+    html += rest;
+
+    Ok(match before {
+        Some(before) => (before, Some(html)),
+        None => (html, None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_calls() {
+        let (call, len) = parse_call(r#" figure(src="/a.png", width=300) }} rest"#, "}}").unwrap();
+        assert_eq!(call.name, "figure");
+        assert_eq!(call.args["src"], "/a.png");
+        assert_eq!(call.args["width"], "300");
+        assert_eq!(len, 35);
+
+        let (call, _) = parse_call("note() %}", "%}").unwrap();
+        assert!(call.args.is_empty());
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        assert!(parse_call(" name }}", "}}").is_none());
+        assert!(parse_call(" a(b) }", "}}").is_none());
+        assert!(parse_call(r#" a(b="c" d="e") }}"#, "}}").is_none());
+        assert!(parse_call(r#" a(b="c) }}"#, "}}").is_none());
+    }
+
+    #[test]
+    fn parses_quoted_values() {
+        let mut rest = r#""say \"hi\" \\ bye", next"#;
+        assert_eq!(value(&mut rest).unwrap(), r#"say "hi" \ bye"#);
+        assert_eq!(rest, ", next");
+
+        let mut rest = "300)";
+        assert_eq!(value(&mut rest).unwrap(), "300");
+        assert_eq!(rest, ")");
+
+        let mut rest = r#""unclosed"#;
+        assert!(value(&mut rest).is_none());
+    }
+
+    #[test]
+    fn finds_the_matching_end() {
+        let text = "{% a() %}x {% inner() %}b{% end %} c{%end%} d";
+        let (start, end) = block_body(text, 9, &[]).unwrap();
+        assert_eq!(&text[9..start], "x {% inner() %}b{% end %} c");
+        assert_eq!(&text[end..], " d");
+
+        assert!(block_body("x {% inner() %}b{% end %}", 0, &[]).is_none());
+        assert_eq!(
+            block_body("{% not a call %}{% end %}", 0, &[]),
+            Some((16, 25))
+        );
+    }
+
+    #[test]
+    fn skips_ends_in_code() {
+        let text = "x `{% end %}` y {% end %}";
+        let code = Range { start: 2, end: 13 };
+        assert_eq!(block_body(text, 0, &[code]), Some((16, 25)));
+    }
+
+    #[test]
+    fn splits_templates_at_the_body() {
+        let (call, _) = parse_call(r#"note(kind="<b>") %}"#, "%}").unwrap();
+        let template = r#"<aside class="{{ kind }}">{{ body }}</aside>{{ title? }}"#;
+        assert_eq!(
+            fill(template, "note.html", &call, true).unwrap(),
+            (
+                r#"<aside class="&lt;b&gt;">"#.to_string(),
+                Some("</aside>".to_string())
+            )
+        );
+        assert!(fill(template, "note.html", &call, false).is_err());
+        assert!(fill("{{ body }}{{ body? }}", "note.html", &call, true).is_err());
+
+        let optional = "<aside>{{ body? }}</aside>";
+        assert_eq!(
+            fill(optional, "note.html", &call, false).unwrap(),
+            ("<aside></aside>".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn reports_body_lines() {
+        let markdown = "\na\n\n{% note() %}\nnever closed";
+        let error = expand(markdown, &[], &mut Snippets::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "body line 3: shortcode `note`: missing `{% end %}`"
+        );
+    }
+}
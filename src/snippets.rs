@@ -0,0 +1,72 @@
+use pulldown_cmark::{Event, Tag};
+
+/// Private use characters around the index of a snippet, so they can't clash with any text.
+const OPEN: char = '\u{E000}';
+const CLOSE: char = '\u{E001}';
+
+/// Html that is rendered before the markdown is parsed (math, shortcodes). It is swapped for
+/// placeholders in the meantime, so that e.g. `a_1 * b_2` is not taken for emphasis.
+#[derive(Default)]
+pub struct Snippets(Vec<String>);
+
+impl Snippets {
+    /// Stores the html and returns the placeholder to put in its place.
+    pub fn add(&mut self, html: String) -> String {
+        self.0.push(html);
+        format!("{}{}{}", OPEN, self.0.len() - 1, CLOSE)
+    }
+
+    fn get(&self, placeholder: &str) -> Option<&String> {
+        let index = placeholder.strip_prefix(OPEN)?.strip_suffix(CLOSE)?;
+        self.0.get(index.parse::<usize>().ok()?)
+    }
+
+    /// Puts the snippets back in place of their placeholders,
+    /// a snippet that is a paragraph of its own replaces the paragraph.
+    pub fn insert<'a>(&self, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        if self.0.is_empty() {
+            return events;
+        }
+
+        let mut out = Vec::with_capacity(events.len());
+        let mut events = events.into_iter().peekable();
+        while let Some(event) = events.next() {
+            let text = match event {
+                Event::Text(text) if text.contains(OPEN) => text,
+                event => {
+                    out.push(event);
+                    continue;
+                }
+            };
+
+            let alone = matches!(out.last(), Some(Event::Start(Tag::Paragraph)))
+                && matches!(events.peek(), Some(Event::End(Tag::Paragraph)));
+            if let Some(html) = self.get(&text).filter(|_| alone) {
+                out.pop();
+                events.next();
+                out.push(Event::Html(html.clone().into()));
+                continue;
+            }
+
+            let mut rest: &str = &text;
+            while let Some((before, after)) = rest.split_once(OPEN) {
+                let (index, after) = match after.split_once(CLOSE) {
+                    Some(split) => split,
+                    None => break,
+                };
+                if !before.is_empty() {
+                    out.push(Event::Text(before.to_string().into()));
+                }
+                if let Some(html) = index.parse::<usize>().ok().and_then(|n| self.0.get(n)) {
+                    out.push(Event::Html(html.clone().into()));
+                }
+                rest = after;
+            }
+            if !rest.is_empty() {
+                out.push(Event::Text(rest.to_string().into()));
+            }
+        }
+
+        out
+    }
+}
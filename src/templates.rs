@@ -0,0 +1,146 @@
+use crate::config::{Config, CONFIG};
+use crate::models::Post;
+use lazy_static::lazy_static;
+use pulldown_cmark::escape::escape_html;
+use serde::Serialize;
+use std::{error::Error, path::Path};
+use tera::{Context, Tera};
+
+/// The default theme, every template can be replaced by one with the same name in
+/// `CONFIG.template_dir`.
+const DEFAULTS: [(&str, &str); 5] = [
+    ("base.html", include_str!("../templates/base.html")),
+    ("page.html", include_str!("../templates/page.html")),
+    ("post.html", include_str!("../templates/post.html")),
+    ("redirect.html", include_str!("../templates/redirect.html")),
+    (
+        "polyring-banner.html",
+        include_str!("../templates/polyring-banner.html"),
+    ),
+];
+
+lazy_static! {
+    /// Parsed on the first render. A broken template fails every render with the same message,
+    /// kept as text since `tera::Error` can't be cloned for each of them.
+    static ref TEMPLATES: Result<Tera, String> = load()
+        .map_err(|e| format!("could not load templates from {}: {}", CONFIG.template_dir, describe(&*e)));
+}
+
+fn load() -> Result<Tera, Box<dyn Error>> {
+    let mut tera = if Path::new(&CONFIG.template_dir).is_dir() {
+        Tera::parse(&format!("{}/**/*.html", CONFIG.template_dir))?
+    } else {
+        Tera::default()
+    };
+
+    let mut defaults = Tera::default();
+    defaults.add_raw_templates(DEFAULTS)?;
+    tera.extend(&defaults)?;
+    tera.build_inheritance_chains()?;
+
+    // the same escaping as in the rendered markdown, which leaves slashes in urls alone
+    tera.autoescape_on(vec![".html"]);
+    tera.set_escape_fn(escape);
+    Ok(tera)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    escape_html(&mut escaped, text).unwrap();
+    escaped
+}
+
+/// Tera keeps the interesting part of an error, e.g. the line in the template, in its sources.
+fn describe(error: &dyn Error) -> String {
+    let mut description = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        description += &format!(": {}", error);
+        source = error.source();
+    }
+    description
+}
+
+pub fn render(name: &str, context: &impl Serialize) -> Result<String, Box<dyn Error>> {
+    let tera = TEMPLATES.as_ref().map_err(|e| e.clone())?;
+    let context = Context::from_serialize(context)?;
+    tera.render(name, &context)
+        .map_err(|e| format!("could not render template {}: {}", name, describe(&e)).into())
+}
+
+#[derive(Serialize)]
+pub struct Link {
+    pub title: String,
+    pub url: String,
+}
+
+/// The buttons below a post or a page of a listing.
+#[derive(Serialize)]
+pub struct Navigation {
+    pub prev: Option<Link>,
+    pub next: Option<Link>,
+}
+
+/// What search engines and link previews show of a page.
+#[derive(Serialize)]
+pub struct Meta {
+    pub description: Option<String>,
+    /// The absolute url of the page.
+    pub canonical: String,
+    /// The OpenGraph type, `article` or `website`.
+    pub kind: &'static str,
+    /// When an article was published in RFC 3339, dates are stored in UTC.
+    pub published: Option<String>,
+}
+
+/// The parts of the config templates get.
+#[derive(Serialize)]
+pub struct Site<'a> {
+    pub url: &'a str,
+    pub title: &'a str,
+    pub author: &'a str,
+    pub description: Option<&'a str>,
+}
+
+impl<'a> Site<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Site {
+            url: &config.url,
+            title: &config.title,
+            author: &config.author,
+            description: config.description.as_deref(),
+        }
+    }
+}
+
+/// What `base.html` and the templates extending it get.
+#[derive(Serialize)]
+pub struct Page<'a> {
+    pub site: Site<'a>,
+    pub title: String,
+    /// The rendered main part of the page.
+    pub body: String,
+    /// Credited in the footer.
+    pub author: String,
+    pub copyright: String,
+    pub post: Option<&'a Post>,
+    pub tags: Vec<Link>,
+    pub navigation: Option<Navigation>,
+    pub meta: Option<Meta>,
+}
+
+impl<'a> Page<'a> {
+    pub fn new(title: String, body: String) -> Self {
+        Page {
+            site: Site::new(&CONFIG),
+            title,
+            body,
+            author: CONFIG.author.clone(),
+            copyright: String::new(),
+            post: None,
+            tags: Vec::new(),
+            navigation: None,
+            meta: None,
+        }
+    }
+}